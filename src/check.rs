@@ -0,0 +1,878 @@
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use serde_yml::libyml::parser::{Event, Parser, ScalarStyle};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+use toml::Spanned;
+
+use crate::{
+    error::TripsuError,
+    index::TypeIndex,
+    io::{self, RulesFormat},
+    log::{info, Logger},
+    pattern::Pattern,
+    rules::{suggest_field, Rules, UriKind},
+    uris::{known_prefix, PrefixMap, Uri, KNOWN_PREFIXES},
+};
+
+/// Severity of a problem found in the rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a rules file, with its line and column when known.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Included file the position refers to, `None` for the checked file.
+    pub file: Option<PathBuf>,
+    pub position: Option<(usize, usize)>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, column)) = self.position {
+            write!(f, "{line}:{column}: ")?;
+        }
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
+/// A rules file being checked, with the positions of its values.
+struct SourceFile {
+    /// Path of an included file, `None` for the checked file.
+    path: Option<PathBuf>,
+    canonical: Option<PathBuf>,
+    source_map: SourceMap,
+}
+
+/// Collects diagnostics, locating the values they refer to in the sources.
+struct Checker {
+    files: Vec<SourceFile>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    /// Report a problem with the value of a field, e.g. `nodes.of_type`,
+    /// located in the first file listing it. The checked file comes first,
+    /// then included files in the order they are included.
+    fn report(&mut self, severity: Severity, field: &str, value: Option<&str>, message: String) {
+        let located = value.and_then(|v| {
+            self.files
+                .iter()
+                .find_map(|f| Some((f.path.clone(), f.source_map.locate(field, v)?)))
+        });
+        let (file, position) = located.unzip();
+        self.diagnostics.push(Diagnostic {
+            severity,
+            message,
+            file: file.flatten(),
+            position,
+        });
+    }
+
+    /// Report a problem with the value of a field in the given file.
+    fn report_in(
+        &mut self,
+        file: usize,
+        severity: Severity,
+        field: &str,
+        value: &str,
+        message: String,
+    ) {
+        let file = &self.files[file];
+        self.diagnostics.push(Diagnostic {
+            severity,
+            message,
+            file: file.path.clone(),
+            position: file.source_map.locate(field, value),
+        });
+    }
+
+    /// Parse a rules file and the files it includes, reporting syntax
+    /// errors and missing included or prefix files in each of them.
+    fn check_sources(&mut self, source: &str, path: &Path, included: Option<PathBuf>) {
+        let format = RulesFormat::from_path(path);
+        let file = self.files.len();
+        self.files.push(SourceFile {
+            path: included.clone(),
+            canonical: path.canonicalize().ok(),
+            source_map: SourceMap::parse(source, format),
+        });
+        let rules = match parse_rules(source, format) {
+            Ok(rules) => rules,
+            Err(diagnostic) => {
+                self.diagnostics.push(Diagnostic {
+                    file: included,
+                    ..diagnostic
+                });
+                return;
+            }
+        };
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for include in rules.includes() {
+            let include_path = dir.join(include);
+            let value = include.to_string_lossy();
+            let Ok(source) = fs::read_to_string(&include_path) else {
+                let message = format!("include: rules file '{value}' not found");
+                self.report_in(file, Severity::Error, "include", &value, message);
+                continue;
+            };
+            // Cyclic includes are reported when merging the files
+            let canonical = include_path.canonicalize().ok();
+            if !self.files.iter().any(|f| f.canonical == canonical) {
+                self.check_sources(&source, &include_path, Some(include_path.clone()));
+            }
+        }
+        for prefix_file in rules.prefix_files() {
+            if let Err(e) = io::parse_turtle_prefixes(&dir.join(prefix_file)) {
+                let value = prefix_file.to_string_lossy();
+                let message = format!("prefixes_from: {e}");
+                self.report_in(file, Severity::Error, "prefixes_from", &value, message);
+            }
+        }
+    }
+
+    fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Get the 1-based line and column of a byte offset in the source.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    (
+        source[..offset].matches('\n').count() + 1,
+        source[line_start..offset].chars().count() + 1,
+    )
+}
+
+/// A scalar of a rules file, with the keys of the mappings containing it.
+/// Keys are themselves scalars of the mapping containing them.
+struct SourceValue {
+    keys: Vec<String>,
+    value: String,
+    position: (usize, usize),
+}
+
+/// Positions of all scalars of a rules file, as reported by the parser.
+struct SourceMap(Vec<SourceValue>);
+
+impl SourceMap {
+    /// Collect the scalars of the source. Invalid sources give the scalars
+    /// found before the first error, which is reported when parsing rules.
+    fn parse(source: &str, format: RulesFormat) -> SourceMap {
+        let mut values = Vec::new();
+        match format {
+            // JSON is parsed as YAML, which it is a subset of
+            RulesFormat::Yaml | RulesFormat::Json => yaml_values(source, &mut values),
+            RulesFormat::Toml => {
+                if let Ok(node) = toml::from_str::<Spanned<TomlNode>>(source) {
+                    toml_values(source, &node, &mut Vec::new(), &mut values);
+                }
+            }
+        }
+        SourceMap(values)
+    }
+
+    /// Find the position of a value listed under the given field,
+    /// e.g. `nodes.of_type`.
+    fn locate(&self, field: &str, value: &str) -> Option<(usize, usize)> {
+        let keys: Vec<&str> = field.split('.').collect();
+        self.0
+            .iter()
+            .find(|v| {
+                v.value == value
+                    && v.keys.len() >= keys.len()
+                    && v.keys.iter().zip(keys.iter()).all(|(a, b)| a == b)
+            })
+            .map(|v| v.position)
+    }
+}
+
+/// Frame of a YAML collection being parsed: the current key of a mapping,
+/// if its value is being parsed, or a sequence.
+enum Frame {
+    Mapping(Option<String>),
+    Sequence,
+}
+
+/// Collect the scalars of a YAML source from the events of the parser.
+fn yaml_values(source: &str, values: &mut Vec<SourceValue>) {
+    let mut parser = Parser::new(Cow::Borrowed(source.as_bytes()));
+    let mut frames: Vec<Frame> = Vec::new();
+    let keys = |frames: &[Frame]| -> Vec<String> {
+        frames
+            .iter()
+            .filter_map(|f| match f {
+                Frame::Mapping(key) => key.clone(),
+                Frame::Sequence => None,
+            })
+            .collect()
+    };
+    // A value of the current mapping was parsed, the next scalar is a key
+    let end_value = |frames: &mut Vec<Frame>| {
+        if let Some(Frame::Mapping(key)) = frames.last_mut() {
+            *key = None;
+        }
+    };
+
+    while let Ok((event, mark)) = parser.parse_next_event() {
+        match event {
+            Event::Scalar(scalar) => {
+                let value = String::from_utf8_lossy(&scalar.value).into_owned();
+                let quoted = matches!(
+                    scalar.style,
+                    ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted
+                );
+                // Positions point to the value, after its quote
+                let position = (
+                    mark.line() as usize + 1,
+                    mark.column() as usize + 1 + usize::from(quoted),
+                );
+                if !frames.is_empty() {
+                    values.push(SourceValue {
+                        keys: keys(&frames),
+                        value: value.clone(),
+                        position,
+                    });
+                }
+                match frames.last_mut() {
+                    Some(Frame::Mapping(key @ None)) => *key = Some(value),
+                    _ => end_value(&mut frames),
+                }
+            }
+            Event::Alias(_) => end_value(&mut frames),
+            Event::MappingStart(_) => frames.push(Frame::Mapping(None)),
+            Event::SequenceStart(_) => frames.push(Frame::Sequence),
+            Event::MappingEnd | Event::SequenceEnd => {
+                frames.pop();
+                end_value(&mut frames);
+            }
+            Event::StreamEnd => break,
+            _ => {}
+        }
+    }
+}
+
+/// Value of a TOML document, keeping the spans of keys and values.
+enum TomlNode {
+    Table(Vec<(Spanned<String>, Spanned<TomlNode>)>),
+    Array(Vec<Spanned<TomlNode>>),
+    String(String),
+    Other,
+}
+
+impl<'de> Deserialize<'de> for TomlNode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NodeVisitor;
+
+        impl<'de> Visitor<'de> for NodeVisitor {
+            type Value = TomlNode;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a TOML value")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<TomlNode, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(key) = map.next_key()? {
+                    entries.push((key, map.next_value()?));
+                }
+                Ok(TomlNode::Table(entries))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<TomlNode, A::Error> {
+                let mut items = Vec::new();
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(TomlNode::Array(items))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<TomlNode, E> {
+                Ok(TomlNode::String(v.to_string()))
+            }
+
+            fn visit_bool<E: de::Error>(self, _: bool) -> Result<TomlNode, E> {
+                Ok(TomlNode::Other)
+            }
+
+            fn visit_i64<E: de::Error>(self, _: i64) -> Result<TomlNode, E> {
+                Ok(TomlNode::Other)
+            }
+
+            fn visit_f64<E: de::Error>(self, _: f64) -> Result<TomlNode, E> {
+                Ok(TomlNode::Other)
+            }
+        }
+
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+/// Collect the keys and strings of a TOML value.
+fn toml_values(
+    source: &str,
+    node: &Spanned<TomlNode>,
+    keys: &mut Vec<String>,
+    values: &mut Vec<SourceValue>,
+) {
+    // Spans of quoted strings and keys start at their quote
+    let position = |offset: usize| {
+        let quoted = source[offset..].starts_with(['"', '\'']);
+        position(source, offset + usize::from(quoted))
+    };
+    match node.get_ref() {
+        TomlNode::Table(entries) => {
+            for (key, value) in entries {
+                values.push(SourceValue {
+                    keys: keys.clone(),
+                    value: key.get_ref().clone(),
+                    position: position(key.span().start),
+                });
+                keys.push(key.get_ref().clone());
+                toml_values(source, value, keys, values);
+                keys.pop();
+            }
+        }
+        TomlNode::Array(items) => {
+            for item in items {
+                toml_values(source, item, keys, values);
+            }
+        }
+        TomlNode::String(value) => values.push(SourceValue {
+            keys: keys.clone(),
+            value: value.clone(),
+            position: position(node.span().start),
+        }),
+        TomlNode::Other => {}
+    }
+}
+
+/// Parse rules in the given format, reporting errors with their position.
+fn parse_rules(source: &str, format: RulesFormat) -> Result<Rules, Diagnostic> {
+    let (message, position) = match format {
+        RulesFormat::Yaml => match serde_yml::from_str(source) {
+            Ok(rules) => return Ok(rules),
+            Err(e) => (e.to_string(), e.location().map(|l| (l.line(), l.column()))),
+        },
+        RulesFormat::Json => match serde_json::from_str(source) {
+            Ok(rules) => return Ok(rules),
+            Err(e) => (e.to_string(), Some((e.line(), e.column()))),
+        },
+        RulesFormat::Toml => match toml::from_str(source) {
+            Ok(rules) => return Ok(rules),
+            Err(e) => (
+                e.message().to_string(),
+                e.span().map(|s| position(source, s.start)),
+            ),
+        },
+    };
+    Err(Diagnostic {
+        severity: Severity::Error,
+        message: suggest_field(&message),
+        file: None,
+        position,
+    })
+}
+
+/// Check the source of the rules file at the given path, reporting every
+/// invalid URI or CURIE and included or prefix files which cannot be read.
+/// Included files are checked too, and the rules are then checked once
+/// merged, locating each value in the file which lists it.
+/// When an index is given, also warn about types which never appear in it.
+/// Warnings are also emitted for predicates listed in several sections
+/// and for prefixes which are never used.
+pub fn check_rules(source: &str, path: &Path, index: Option<&TypeIndex>) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        files: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    checker.check_sources(source, path, None);
+    if checker.has_errors() {
+        return checker.diagnostics;
+    }
+
+    let file = path.canonicalize().unwrap_or(path.to_path_buf());
+    let mut stack = Vec::from_iter(path.canonicalize().ok());
+    let merged = io::load_rules_source(source, path, &file, &mut stack).and_then(|value| {
+        serde_yml::from_value::<Rules>(value)
+            .map_err(|e| TripsuError::Rules(suggest_field(&e.to_string())))
+    });
+    let mut rules = match merged {
+        Ok(rules) => rules,
+        Err(e) => {
+            checker.report(Severity::Error, "", None, e.to_string());
+            return checker.diagnostics;
+        }
+    };
+
+    // Only declared prefixes are expected to be used
+    let declared_prefixes = rules.prefixes().cloned();
+    let mut namespaces = HashMap::new();
+    let mut unresolved = false;
+    for name in rules.use_prefixes() {
+        if known_prefix(name).is_none() {
+            unresolved = true;
+            let known: Vec<_> = KNOWN_PREFIXES.iter().map(|(p, _)| *p).collect();
+            checker.report(
+                Severity::Error,
+                "use_prefixes",
+                Some(name),
+                format!(
+                    "use_prefixes: unknown prefix '{name}', known prefixes are: {}",
+                    known.join(", ")
+                ),
+            );
+        }
+    }
+    // Prefix files were checked in each source and are relative to it
+    for prefix_file in rules.prefix_files() {
+        match io::parse_turtle_prefixes(prefix_file) {
+            Ok(prefixes) => namespaces.extend(prefixes),
+            Err(e) => {
+                unresolved = true;
+                checker.report(Severity::Error, "", None, format!("prefixes_from: {e}"));
+            }
+        }
+    }
+    if unresolved || rules.import_prefixes(namespaces).is_err() {
+        return checker.diagnostics;
+    }
+
+    // Prefixes are checked one by one to report all invalid ones
+    let mut prefix_map = None;
+    if let Some(prefixes) = rules.prefixes() {
+        for (name, uri) in prefixes.iter() {
+            let single = HashMap::from([(name.clone(), uri.clone())]);
+            if let Err(e) = PrefixMap::from_hashmap(&single) {
+                checker.report(
+                    Severity::Error,
+                    "prefixes",
+                    Some(uri),
+                    format!("prefixes: {e}"),
+                );
+            }
+        }
+        prefix_map = PrefixMap::from_hashmap(prefixes).ok();
+        if prefix_map.is_none() {
+            return checker.diagnostics;
+        }
+    }
+
+    let expand = |uri: &str| -> Result<String, anyhow::Error> {
+        let uri = Uri::try_from(uri.to_string())?;
+        Ok(match &prefix_map {
+            Some(prefixes) => uri.expand(prefixes)?,
+            None => uri,
+        }
+        .to_string())
+    };
+
+    let mut expanded = Vec::new();
+    for entry in rules.uri_entries() {
+        match expand(entry.uri) {
+            Ok(uri) => expanded.push((entry, uri)),
+            Err(e) => checker.report(
+                Severity::Error,
+                entry.field,
+                Some(entry.uri),
+                format!("{}: {e}", entry.field),
+            ),
+        }
+    }
+    let empty_prefixes = PrefixMap::new();
+    for (field, pattern) in rules.iri_patterns() {
+        if let Err(e) = pattern.expand_iri(prefix_map.as_ref().unwrap_or(&empty_prefixes)) {
+            let value = match pattern {
+                Pattern::Prefix(p) => Some(p.as_str()),
+                Pattern::Regex(_) => None,
+            };
+            checker.report(Severity::Error, field, value, format!("{field}: {e}"));
+        }
+    }
+    // Remaining checks which do not concern single URIs
+    if !checker.has_errors() {
+        if let Err(e) = rules.check_uris() {
+            checker.report(Severity::Error, "", None, e.to_string());
+        }
+    }
+
+    if let Some(index) = index {
+        let types: HashSet<&str> = index.types.iter().map(String::as_str).collect();
+        for (entry, uri) in expanded.iter() {
+            if entry.kind == UriKind::Type && !types.contains(uri.as_str()) {
+                checker.report(
+                    Severity::Warning,
+                    entry.field,
+                    Some(entry.uri),
+                    format!(
+                        "{}: type '{}' never appears in the index",
+                        entry.field, entry.uri
+                    ),
+                );
+            }
+        }
+    }
+
+    // Paths of propagations are not rules on predicates, and object
+    // conditions only apply to some values of their predicate
+    let mut sections = HashMap::<&str, Vec<&str>>::new();
+    for (entry, uri) in expanded.iter() {
+        let conditional = ["nodes.propagate", "objects.on_object"].contains(&entry.field);
+        if entry.kind == UriKind::Predicate && !conditional {
+            let fields = sections.entry(uri).or_default();
+            if !fields.contains(&entry.field) {
+                fields.push(entry.field);
+            }
+        }
+    }
+    for (entry, uri) in expanded.iter() {
+        let Some(fields) = sections.remove(uri.as_str()) else {
+            continue;
+        };
+        if fields.len() > 1 {
+            checker.report(
+                Severity::Warning,
+                entry.field,
+                Some(entry.uri),
+                format!(
+                    "predicate '{}' is listed in several sections: {}",
+                    entry.uri,
+                    fields.join(", ")
+                ),
+            );
+        }
+    }
+
+    if let Some(prefixes) = declared_prefixes {
+        let compact_uris: Vec<&str> = expanded
+            .iter()
+            .map(|(entry, _)| entry.uri)
+            .chain(
+                rules
+                    .iri_patterns()
+                    .into_iter()
+                    .filter_map(|(_, p)| match p {
+                        Pattern::Prefix(p) => Some(p.as_str()),
+                        Pattern::Regex(_) => None,
+                    }),
+            )
+            .filter(|uri| !uri.starts_with('<'))
+            .collect();
+        for (name, uri) in prefixes.iter() {
+            let Some(name) = name else {
+                continue;
+            };
+            let curie_start = format!("{name}:");
+            if !compact_uris.iter().any(|u| u.starts_with(&curie_start)) {
+                checker.report(
+                    Severity::Warning,
+                    "prefixes",
+                    Some(uri),
+                    format!("prefix '{name}' is never used"),
+                );
+            }
+        }
+    }
+
+    // Report in the order of the files, problems without position last
+    checker
+        .diagnostics
+        .sort_by_key(|d| (d.position.is_none(), d.file.clone(), d.position));
+    checker.diagnostics
+}
+
+/// Check a rules file and print all diagnostics.
+/// Fails if any error was found.
+pub fn check_rules_file(
+    log: &Logger,
+    rules_path: &Path,
+    index_path: &Option<PathBuf>,
+) -> Result<(), TripsuError> {
+    let source = fs::read_to_string(rules_path).map_err(|e| {
+        TripsuError::Rules(format!(
+            "Cannot open rules file '{:?}': '{}'.",
+            rules_path, e
+        ))
+    })?;
+    let index = index_path.as_deref().map(io::parse_index).transpose()?;
+
+    let diagnostics = check_rules(&source, rules_path, index.as_ref());
+    for d in diagnostics.iter() {
+        let path = d.file.as_deref().unwrap_or(rules_path);
+        println!("{}:{d}", path.display());
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    info!(
+        log,
+        "{errors} error(s), {} warning(s)",
+        diagnostics.len() - errors
+    );
+    if errors > 0 {
+        return Err(TripsuError::Rules(format!(
+            "Rules file contains {errors} error(s)."
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    fn messages(source: &str, index: Option<&TypeIndex>) -> Vec<String> {
+        check_rules(source, Path::new("tests/data/check.yaml"), index)
+            .iter()
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    #[rstest]
+    // Valid rules
+    #[case(
+        r#"
+objects:
+  on_predicate: ["<urn:hasName>"]
+"#,
+        &[]
+    )]
+    // Invalid yaml
+    #[case(
+        r#"
+nodes:
+  of_type: ["<urn:Person>"
+"#,
+        &["4:1: error: "]
+    )]
+    // Unknown detector
+    #[case("detectors: [passport]\n", &[r"1:13: error: detectors.\[0\]: unknown variant `passport`"])]
+    // All invalid URIs are reported with their position
+    #[case(
+        r#"
+nodes:
+  of_type:
+    - "Person"
+objects:
+  on_predicate:
+    - "<not an iri>"
+"#,
+        &[
+            "4:8: error: nodes.of_type: The given IRI",
+            "7:8: error: objects.on_predicate: ",
+        ]
+    )]
+    // Undefined prefix
+    #[case(
+        r#"
+prefixes:
+  ex: "<http://example.org/>"
+nodes:
+  of_type: ["ex:Person", "foaf:Person"]
+"#,
+        &["5:27: error: nodes.of_type: Invalid prefix: foaf:Person"]
+    )]
+    // Well-known prefixes
+    #[case(
+        r#"
+use_prefixes: [foaf, vcard]
+nodes:
+  of_type: ["foaf:Person"]
+"#,
+        &["2:22: error: use_prefixes: unknown prefix 'vcard', known prefixes are: rdf, rdfs"]
+    )]
+    #[case("use_prefixes: [foaf]\nnodes:\n  of_type: [\"foaf:Person\"]\n", &[])]
+    // Missing include
+    #[case(
+        "include: [rules.yaml, missing.yaml]\n",
+        &["1:23: error: include: rules file 'missing.yaml' not found"]
+    )]
+    // Object condition without fields
+    #[case(
+        "objects:\n  on_object: [{}]\n",
+        &["error: objects.on_object: an entry must set at least one condition."]
+    )]
+    // Propagation without predicates
+    #[case(
+        "nodes:\n  propagate:\n    - {from_type: \"<urn:A>\", path: [], as_type: \"<urn:B>\"}\n",
+        &["3:7: error: nodes.propagate"]
+    )]
+    // Base strategy without base
+    #[case(
+        "iris:\n  strategies: [base]\n",
+        &["error: The 'base' IRI strategy requires 'iris.base' to be set."]
+    )]
+    fn check_errors(#[case] source: &str, #[case] expected: &[&str]) {
+        let messages = messages(source, None);
+        assert_eq!(messages.len(), expected.len(), "{messages:?}");
+        for (message, expected) in messages.iter().zip(expected) {
+            assert!(message.starts_with(expected), "{message}");
+        }
+    }
+
+    #[rstest]
+    // Type missing from the index
+    #[case(
+        r#"
+nodes:
+  of_type: ["<urn:Person>", "<urn:Robot>"]
+"#,
+        &["3:30: warning: nodes.of_type: type '<urn:Robot>' never appears in the index"]
+    )]
+    // Predicate in several sections
+    #[case(
+        r#"
+objects:
+  on_predicate: ["<urn:hasName>"]
+  on_type_predicate:
+    "<urn:Person>": ["<urn:hasName>"]
+"#,
+        &["3:19: warning: predicate '<urn:hasName>' is listed in several sections: objects.on_predicate, objects.on_type_predicate"]
+    )]
+    // Values are located in the field they are listed in
+    #[case(
+        r#"
+exceptions:
+  of_type: ["<urn:hasName>"]
+objects:
+  on_predicate: ["<urn:hasName>"]
+  on_type_predicate:
+    "<urn:Person>": ["<urn:hasName>"]
+"#,
+        &[
+            "3:14: warning: exceptions.of_type: type '<urn:hasName>' never appears in the index",
+            "5:19: warning: predicate '<urn:hasName>' is listed in several sections: objects.on_predicate, objects.on_type_predicate",
+        ]
+    )]
+    // Object conditions only apply to some values of their predicate
+    #[case(
+        r#"
+objects:
+  on_predicate: ["<urn:hasName>"]
+  on_object:
+    - predicate: "<urn:hasName>"
+      value: {equals: "ACME"}
+"#,
+        &[]
+    )]
+    // Unused prefix, comments are ignored
+    #[case(
+        r#"
+# foaf: is unused
+prefixes:
+  ex: "<http://example.org/>"
+  foaf: "<http://xmlns.com/foaf/0.1/>"
+nodes:
+  matching:
+    - prefix: "ex:"
+"#,
+        &["5:10: warning: prefix 'foaf' is never used"]
+    )]
+    fn check_warnings(#[case] source: &str, #[case] expected: &[&str]) {
+        let index = TypeIndex::from_iter(vec![("<urn:Alice>", "<urn:Person>")]);
+        assert_eq!(messages(source, Some(&index)), expected);
+    }
+
+    #[rstest]
+    // Valid json
+    #[case("rules.json", r#"{"objects": {"on_predicate": ["<urn:hasName>"]}}"#, &[])]
+    // Typo in json
+    #[case(
+        "rules.json",
+        r#"{"objects": {"on_predicates": []}}"#,
+        &["1:28: error: unknown field `on_predicates` (did you mean `on_predicate`?)"]
+    )]
+    // Invalid URI in json
+    #[case(
+        "rules.json",
+        "{\"nodes\": {\n  \"of_type\": [\"Person\"]}}",
+        &["2:16: error: nodes.of_type: The given IRI"]
+    )]
+    // Valid toml
+    #[case("rules.toml", "[objects]\non_predicate = [\"<urn:hasName>\"]\n", &[])]
+    // Typo in toml
+    #[case(
+        "rules.toml",
+        "[objects]\non_predicates = []\n",
+        &["2:1: error: unknown field `on_predicates` (did you mean `on_predicate`?)"]
+    )]
+    // Invalid URI in toml
+    #[case(
+        "rules.toml",
+        "[nodes]\nof_type = [\"Person\"]\n",
+        &["2:13: error: nodes.of_type: The given IRI"]
+    )]
+    // Prefixes in toml
+    #[case(
+        "rules.toml",
+        "[prefixes]\nex = \"<http://example.org/>\"\n[nodes]\nof_type = [\"ex:Person\", \"foaf:Person\"]\n",
+        &["4:26: error: nodes.of_type: Invalid prefix: foaf:Person"]
+    )]
+    fn check_formats(#[case] path: &str, #[case] source: &str, #[case] expected: &[&str]) {
+        let messages: Vec<String> = check_rules(source, Path::new(path), None)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(messages.len(), expected.len(), "{messages:?}");
+        for (message, expected) in messages.iter().zip(expected) {
+            assert!(message.starts_with(expected), "{message}");
+        }
+    }
+
+    #[test]
+    // Included files are checked, and problems located in the file listing the value
+    fn check_includes() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("base.yaml"),
+            "nodes:\n  of_type: [\"<not an iri>\"]\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("broken.yaml"), "nodes: [\n").unwrap();
+        let path = dir.path().join("rules.yaml");
+        let located = |source: &str| -> Vec<(Option<PathBuf>, String)> {
+            check_rules(source, &path, None)
+                .into_iter()
+                .map(|d| (d.file.clone(), d.to_string()))
+                .collect()
+        };
+
+        let diagnostics = located("include: [base.yaml]\nnodes:\n  of_type: [\"Person\"]\n");
+        assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].0, None);
+        assert!(diagnostics[0].1.starts_with("3:14: error: nodes.of_type"));
+        assert_eq!(diagnostics[1].0, Some(dir.path().join("base.yaml")));
+        assert!(diagnostics[1].1.starts_with("2:14: error: nodes.of_type"));
+
+        let diagnostics = located("include: [broken.yaml]\n");
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].0, Some(dir.path().join("broken.yaml")));
+        assert!(diagnostics[0]
+            .1
+            .starts_with("1:8: error: nodes: invalid type"));
+    }
+}
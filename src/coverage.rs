@@ -0,0 +1,248 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    io::Write,
+    path::Path,
+};
+
+use crate::{
+    error::TripsuError,
+    io::{self, ErrorHandler},
+    log::{info, Logger},
+    model::TripleMask,
+    rdf_types::*,
+    rules::{explain_rules, strip_brackets, Explanation, RuleEntry, Rules, UriKind},
+};
+
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+/// Number of matches of a rule entry.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RuleCount {
+    pub rule: &'static str,
+    /// Type of type-predicate rules.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub of_type: Option<String>,
+    pub value: String,
+    pub matches: usize,
+}
+
+/// How much of a dataset the rules touch, and which predicates
+/// and types of the data are not mentioned by any rule.
+#[derive(Serialize, Debug)]
+pub struct CoverageReport {
+    pub triples: usize,
+    pub pseudonymized_subjects: usize,
+    pub pseudonymized_objects: usize,
+    pub rules: Vec<RuleCount>,
+    pub uncovered_predicates: Vec<String>,
+    pub uncovered_types: Vec<String>,
+}
+
+/// Accumulates the explanations of all triples of a dataset.
+#[derive(Default)]
+pub struct Coverage {
+    triples: usize,
+    subjects: usize,
+    objects: usize,
+    counts: BTreeMap<RuleEntry, usize>,
+    predicates: BTreeSet<String>,
+    types: BTreeSet<String>,
+}
+
+impl Coverage {
+    pub fn add(&mut self, triple: &TripleView, explanation: &Explanation) {
+        self.triples += 1;
+        if explanation.mask.is_set(&TripleMask::SUBJECT) {
+            self.subjects += 1;
+        }
+        if explanation.mask.is_set(&TripleMask::OBJECT) {
+            self.objects += 1;
+        }
+        for m in explanation.matches.iter() {
+            *self.counts.entry(m.entry()).or_default() += 1;
+        }
+
+        self.predicates.insert(triple.predicate.to_string());
+        if triple.predicate.iri == RDF_TYPE {
+            self.types.insert(triple.object.to_string());
+        }
+    }
+
+    /// Build the report, listing all rules with their number of matches,
+    /// and predicates and types of the data which are neither listed in
+    /// the rules nor matched by a predicate pattern.
+    pub fn report(mut self, rules: &Rules) -> CoverageReport {
+        // Rules which never matched are reported too
+        for entry in rules.rule_entries() {
+            self.counts.entry(entry).or_default();
+        }
+        let entries = rules.uri_entries();
+        let listed = |kind: UriKind| -> HashSet<&str> {
+            entries
+                .iter()
+                .filter(|e| e.kind == kind)
+                .map(|e| e.uri)
+                .collect()
+        };
+        let (predicates, types) = (listed(UriKind::Predicate), listed(UriKind::Type));
+        let patterns = rules.iri_patterns();
+        let matches_pattern = |predicate: &str| {
+            patterns.iter().any(|(field, p)| {
+                *field == "objects.on_predicate_matching" && p.is_match(strip_brackets(predicate))
+            })
+        };
+
+        CoverageReport {
+            triples: self.triples,
+            pseudonymized_subjects: self.subjects,
+            pseudonymized_objects: self.objects,
+            rules: self
+                .counts
+                .into_iter()
+                .map(|((rule, of_type, value), matches)| RuleCount {
+                    rule,
+                    of_type,
+                    value,
+                    matches,
+                })
+                .collect(),
+            uncovered_predicates: self
+                .predicates
+                .into_iter()
+                .filter(|p| !predicates.contains(p.as_str()) && !matches_pattern(p))
+                .collect(),
+            uncovered_types: self
+                .types
+                .into_iter()
+                .filter(|t| !types.contains(t.as_str()))
+                .collect(),
+        }
+    }
+}
+
+/// Evaluate the rules on all input triples without pseudonymizing them,
+/// and write a json report of the matches of each rule to the output.
+pub fn coverage_graph(
+    log: &Logger,
+    input: &Path,
+    rules_path: &Path,
+    output: &Path,
+    index_path: &Path,
+    errors: &mut ErrorHandler,
+) -> Result<(), TripsuError> {
+    let rules = io::parse_rules(rules_path)?;
+    let type_index = io::parse_index(index_path)?;
+
+    let buf_input = io::get_reader(input)?;
+    let mut coverage = Coverage::default();
+
+    io::parse_ntriples_with(buf_input, errors, &mut |t: TripleView| {
+        let explanation = explain_rules(&t, &rules, &type_index);
+        coverage.add(&t, &explanation);
+        Ok(())
+    })?;
+    errors.finish(log)?;
+
+    let report = coverage.report(&rules);
+    info!(
+        log,
+        "{} triples: {} subjects and {} objects would be pseudonymized",
+        report.triples,
+        report.pseudonymized_subjects,
+        report.pseudonymized_objects
+    );
+    let mut buf_output = io::get_writer(output)?;
+    serde_json::to_writer_pretty(&mut buf_output, &report).map_err(std::io::Error::from)?;
+    buf_output.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::TypeIndex;
+    use rio_api::parser::TriplesParser;
+    use rio_turtle::{TurtleError, TurtleParser};
+
+    #[test]
+    // Rules are counted and unmentioned predicates and types reported
+    fn coverage_report() {
+        let rules: Rules = serde_yml::from_str(
+            r#"
+            nodes:
+              of_type: ["<urn:Person>", "<urn:Robot>"]
+            objects:
+              on_predicate: ["<urn:hasLastName>"]
+              on_predicate_matching:
+                - prefix: "<urn:private:>"
+              on_type_predicate:
+                "<urn:Person>": ["<urn:hasName>"]
+                "<urn:Organization>": ["<urn:hasName>"]
+            "#,
+        )
+        .unwrap();
+        let rules = rules.expand_rules_curie().unwrap();
+        let index = TypeIndex::from_iter(vec![
+            ("<urn:Alice>", "<urn:Person>"),
+            ("<urn:ACME>", "<urn:Organization>"),
+        ]);
+        let triples = r#"
+            <urn:Alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Person> .
+            <urn:ACME> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Organization> .
+            <urn:Mill> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Machine> .
+            <urn:Alice> <urn:hasLastName> "Foobar" .
+            <urn:Alice> <urn:private:salary> "100" .
+            <urn:ACME> <urn:hasName> "ACME" .
+        "#;
+
+        let mut coverage = Coverage::default();
+        TurtleParser::new(triples.as_ref(), None)
+            .parse_all(&mut |t| {
+                coverage.add(&t, &explain_rules(&t, &rules, &index));
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+        let report = coverage.report(&rules);
+
+        assert_eq!(report.triples, 6);
+        assert_eq!(report.pseudonymized_subjects, 3);
+        assert_eq!(report.pseudonymized_objects, 3);
+        let count = |rule, of_type: Option<&str>, value: &str, matches| RuleCount {
+            rule,
+            of_type: of_type.map(String::from),
+            value: value.into(),
+            matches,
+        };
+        // Rules which never matched are counted, type-predicate rules by type
+        assert_eq!(
+            report.rules,
+            vec![
+                count("nodes.of_type", None, "<urn:Person>", 3),
+                count("nodes.of_type", None, "<urn:Robot>", 0),
+                count("objects.on_predicate", None, "<urn:hasLastName>", 1),
+                count(
+                    "objects.on_predicate_matching",
+                    None,
+                    "prefix: urn:private:",
+                    1
+                ),
+                count(
+                    "objects.on_type_predicate",
+                    Some("<urn:Organization>"),
+                    "<urn:hasName>",
+                    1
+                ),
+                count(
+                    "objects.on_type_predicate",
+                    Some("<urn:Person>"),
+                    "<urn:hasName>",
+                    0
+                ),
+            ]
+        );
+        assert_eq!(report.uncovered_predicates, vec![format!("<{RDF_TYPE}>")]);
+        assert_eq!(report.uncovered_types, vec!["<urn:Machine>"]);
+    }
+}
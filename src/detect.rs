@@ -0,0 +1,271 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    ops::Range,
+    sync::OnceLock,
+};
+
+/// Built-in recognizers for personal information in literal values.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Detector {
+    Email,
+    Phone,
+    Iban,
+    CreditCard,
+    IpAddress,
+    Ahv,
+}
+
+static EMAIL: OnceLock<Regex> = OnceLock::new();
+static PHONE: OnceLock<Regex> = OnceLock::new();
+static IBAN: OnceLock<Regex> = OnceLock::new();
+static CREDIT_CARD: OnceLock<Regex> = OnceLock::new();
+static IPV4: OnceLock<Regex> = OnceLock::new();
+static IPV6: OnceLock<Regex> = OnceLock::new();
+static AHV: OnceLock<Regex> = OnceLock::new();
+
+/// Get a lazily compiled regex.
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+impl Detector {
+    /// Name of the detector as used in the rules file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Detector::Email => "email",
+            Detector::Phone => "phone",
+            Detector::Iban => "iban",
+            Detector::CreditCard => "credit_card",
+            Detector::IpAddress => "ip_address",
+            Detector::Ahv => "ahv",
+        }
+    }
+
+    /// Find the byte ranges of all detected values in the text.
+    pub fn find_spans(&self, text: &str) -> Vec<Range<usize>> {
+        match self {
+            Detector::Email => find_valid(
+                regex(
+                    &EMAIL,
+                    r"[A-Za-z0-9._%+\-]+@[A-Za-z0-9\-]+(?:\.[A-Za-z0-9\-]+)*\.[A-Za-z]{2,}",
+                ),
+                text,
+                |_| true,
+            ),
+            Detector::Phone => {
+                find_valid(regex(&PHONE, r"(?:\+\d|\b0)[\d \-/().]{6,}\d"), text, |s| {
+                    (9..=15).contains(&digits(s).len()) && !is_decimal(s)
+                })
+            }
+            Detector::Iban => find_valid(
+                regex(&IBAN, r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]){11,30}\b"),
+                text,
+                is_valid_iban,
+            ),
+            Detector::CreditCard => find_valid(
+                regex(&CREDIT_CARD, r"\b\d(?:[ \-]?\d){12,18}\b"),
+                text,
+                |s| is_card_number(&digits(s)),
+            ),
+            Detector::IpAddress => {
+                let mut spans =
+                    find_valid(regex(&IPV4, r"\b\d{1,3}(?:\.\d{1,3}){3}\b"), text, |s| {
+                        s.parse::<Ipv4Addr>().is_ok()
+                    });
+                spans.extend(
+                    regex(&IPV6, r"[0-9A-Fa-f]{1,4}(?::[0-9A-Fa-f]{0,4}){2,7}")
+                        .find_iter(text)
+                        .filter(|m| is_standalone(text, m.range()) && is_valid_ipv6(m.as_str()))
+                        .map(|m| m.range()),
+                );
+                spans
+            }
+            Detector::Ahv => find_valid(
+                regex(&AHV, r"\b756[. ]?\d{4}[. ]?\d{4}[. ]?\d{2}\b"),
+                text,
+                |s| ean13(&digits(s)),
+            ),
+        }
+    }
+
+    /// Check if the text contains a detected value.
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.find_spans(text).is_empty()
+    }
+}
+
+/// Return the ranges of regex matches accepted by the validator.
+fn find_valid(re: &Regex, text: &str, is_valid: impl Fn(&str) -> bool) -> Vec<Range<usize>> {
+    re.find_iter(text)
+        .filter(|m| is_valid(m.as_str()))
+        .map(|m| m.range())
+        .collect()
+}
+
+/// Extract the decimal digits of a string.
+fn digits(s: &str) -> Vec<u32> {
+    s.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+/// Check if the text is a decimal number such as `0.123456789`.
+fn is_decimal(s: &str) -> bool {
+    let s = s.strip_prefix('+').unwrap_or(s);
+    s.matches('.').count() == 1 && s.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Check if a match is not part of a longer word or path, e.g. `std::vec`.
+fn is_standalone(text: &str, range: Range<usize>) -> bool {
+    let is_part = |c: char| c.is_alphanumeric() || c == '_' || c == ':';
+    !text[..range.start].chars().next_back().is_some_and(is_part)
+        && !text[range.end..].chars().next().is_some_and(is_part)
+}
+
+/// Check if the text is an IPv6 address with hex groups on both sides of `::`.
+fn is_valid_ipv6(s: &str) -> bool {
+    let groups = match s.split_once("::") {
+        Some((head, tail)) => !head.is_empty() && !tail.is_empty(),
+        None => true,
+    };
+    groups && s.parse::<Ipv6Addr>().is_ok()
+}
+
+/// Check the issuer prefix (IIN), length and Luhn checksum of a payment card number.
+fn is_card_number(digits: &[u32]) -> bool {
+    let prefix = |n: usize| digits.iter().take(n).fold(0, |acc, d| acc * 10 + d);
+    let lengths = match (prefix(1), prefix(2), prefix(3), prefix(4)) {
+        // Visa
+        (4, ..) => vec![13, 16, 19],
+        // Mastercard
+        (_, 51..=55, ..) | (.., 2221..=2720) => vec![16],
+        // American Express
+        (_, 34 | 37, ..) => vec![15],
+        // Diners Club
+        (_, 36 | 38 | 39, ..) | (_, _, 300..=305, _) => (14..=19).collect(),
+        // Discover, UnionPay and JCB
+        (_, 62 | 65, ..) | (_, _, 644..=649, _) | (.., 6011 | 3528..=3589) => (16..=19).collect(),
+        // Maestro
+        (_, 50 | 56..=58, ..) => (12..=19).collect(),
+        _ => return false,
+    };
+    lengths.contains(&digits.len()) && luhn(digits)
+}
+
+/// Luhn checksum used by payment card numbers.
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match i % 2 {
+            0 => d,
+            _ if d * 2 > 9 => d * 2 - 9,
+            _ => d * 2,
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+/// EAN-13 check digit used by Swiss AHV numbers.
+fn ean13(digits: &[u32]) -> bool {
+    if digits.len() != 13 {
+        return false;
+    }
+    let sum: u32 = digits[..12]
+        .iter()
+        .enumerate()
+        .map(|(i, &d)| if i % 2 == 0 { d } else { d * 3 })
+        .sum();
+    (10 - sum % 10) % 10 == digits[12]
+}
+
+/// ISO 13616 mod-97 checksum of an IBAN.
+fn is_valid_iban(s: &str) -> bool {
+    let iban: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&iban.len()) {
+        return false;
+    }
+    let (head, tail) = iban.split_at(4);
+    let remainder = tail
+        .chars()
+        .chain(head.chars())
+        .filter_map(|c| c.to_digit(36))
+        .fold(0u32, |acc, d| {
+            let acc = if d > 9 { acc * 100 + d } else { acc * 10 + d };
+            acc % 97
+        });
+    remainder == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        Detector::Email,
+        "Contact: alice@example.org.",
+        Some("alice@example.org")
+    )]
+    #[case(Detector::Email, "alice at example dot org", None)]
+    #[case(Detector::Phone, "Call +41 44 123 45 67 now", Some("+41 44 123 45 67"))]
+    #[case(Detector::Phone, "Born 2024-01-01", None)]
+    #[case(Detector::Phone, "ratio 0.123456789", None)]
+    #[case(Detector::Phone, "delta +1.23456789", None)]
+    #[case(Detector::Phone, "count 123456789012", None)]
+    #[case(
+        Detector::Iban,
+        "IBAN CH93 0076 2011 6238 5295 7",
+        Some("CH93 0076 2011 6238 5295 7")
+    )]
+    #[case(Detector::Iban, "IBAN CH94 0076 2011 6238 5295 7", None)]
+    #[case(
+        Detector::CreditCard,
+        "Card 4111 1111 1111 1111",
+        Some("4111 1111 1111 1111")
+    )]
+    #[case(Detector::CreditCard, "Card 4111 1111 1111 1112", None)]
+    #[case(
+        Detector::CreditCard,
+        "Card 5555 5555 5555 4444",
+        Some("5555 5555 5555 4444")
+    )]
+    #[case(
+        Detector::CreditCard,
+        "Card 3782 822463 10005",
+        Some("3782 822463 10005")
+    )]
+    // Millisecond timestamp passing the Luhn checksum
+    #[case(Detector::CreditCard, "at 1718000000006", None)]
+    // Plain number with an unknown issuer passing the Luhn checksum
+    #[case(Detector::CreditCard, "id 1234567890123460", None)]
+    // Visa prefix with an invalid length
+    #[case(Detector::CreditCard, "id 41111111111111", None)]
+    #[case(Detector::IpAddress, "from 192.168.1.20", Some("192.168.1.20"))]
+    #[case(
+        Detector::IpAddress,
+        "from fe80::1ff:fe23:4567:890a",
+        Some("fe80::1ff:fe23:4567:890a")
+    )]
+    #[case(Detector::IpAddress, "version 999.1.1.1 at 12:30", None)]
+    #[case(Detector::IpAddress, "use std::vec::Vec;", None)]
+    #[case(Detector::IpAddress, "call a::b::c() or std::fmt", None)]
+    #[case(Detector::IpAddress, "ratio 0.123456789", None)]
+    #[case(
+        Detector::IpAddress,
+        "host 2001:db8::8a2e:370:7334.",
+        Some("2001:db8::8a2e:370:7334")
+    )]
+    #[case(Detector::Ahv, "AHV 756.1234.5678.97", Some("756.1234.5678.97"))]
+    #[case(Detector::Ahv, "AHV 756.1234.5678.98", None)]
+    fn detectors(#[case] detector: Detector, #[case] text: &str, #[case] expected: Option<&str>) {
+        let spans = detector.find_spans(text);
+        match expected {
+            Some(value) => assert_eq!(&text[spans[0].clone()], value),
+            None => assert!(spans.is_empty()),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use rio_turtle::TurtleError;
+use std::{error::Error, fmt, io};
+
+use crate::crypto::PseudoError;
+
+/// Errors raised by tripsu, by category. Each category exits the
+/// command line with its own code:
+///
+/// | Code | Category                                     |
+/// |------|----------------------------------------------|
+/// | 1    | Unexpected failure                           |
+/// | 2    | [`TripsuError::Usage`]: invalid arguments    |
+/// | 3    | [`TripsuError::Io`]: input or output streams |
+/// | 4    | [`TripsuError::Parse`]: input triples        |
+/// | 5    | [`TripsuError::Rules`]: rules files          |
+/// | 6    | [`TripsuError::Index`]: index file           |
+/// | 7    | [`TripsuError::Key`]: secret key file        |
+/// | 8    | [`TripsuError::Pseudo`]: pseudonymization    |
+#[derive(Debug)]
+pub enum TripsuError {
+    Usage(String),
+    Io(String),
+    Parse(String),
+    Rules(String),
+    Index(String),
+    Key(String),
+    Pseudo(PseudoError),
+}
+
+impl TripsuError {
+    /// Exit code of the command line for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Usage(_) => 2,
+            Self::Io(_) => 3,
+            Self::Parse(_) => 4,
+            Self::Rules(_) => 5,
+            Self::Index(_) => 6,
+            Self::Key(_) => 7,
+            Self::Pseudo(_) => 8,
+        }
+    }
+}
+
+impl fmt::Display for TripsuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(msg) => write!(f, "Usage error: {msg}"),
+            Self::Io(msg) => write!(f, "I/O error: {msg}"),
+            Self::Parse(msg) => write!(f, "Parsing error: {msg}"),
+            Self::Rules(msg) => write!(f, "Rules error: {msg}"),
+            Self::Index(msg) => write!(f, "Index error: {msg}"),
+            Self::Key(msg) => write!(f, "Key error: {msg}"),
+            Self::Pseudo(e) => write!(f, "Pseudonymization error: {e}"),
+        }
+    }
+}
+
+impl Error for TripsuError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Pseudo(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TripsuError {
+    fn from(err: io::Error) -> Self {
+        TripsuError::Io(err.to_string())
+    }
+}
+
+impl From<TurtleError> for TripsuError {
+    fn from(err: TurtleError) -> Self {
+        TripsuError::Parse(err.to_string())
+    }
+}
+
+impl From<PseudoError> for TripsuError {
+    fn from(err: PseudoError) -> Self {
+        TripsuError::Pseudo(err)
+    }
+}
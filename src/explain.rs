@@ -0,0 +1,111 @@
+use rio_api::parser::TriplesParser;
+use rio_turtle::TurtleError;
+use serde::Serialize;
+use std::{io::Write, path::Path};
+
+use crate::{
+    error::TripsuError,
+    index::TypeIndex,
+    io,
+    log::Logger,
+    model::TripleMask,
+    rdf_types::*,
+    rules::{explain_rules, RuleMatch, Rules},
+};
+
+/// Explanation of the pseudonymization of a single triple.
+#[derive(Serialize)]
+struct TripleExplanation<'a> {
+    triple: String,
+    subject: bool,
+    object: bool,
+    matches: &'a [RuleMatch],
+}
+
+// explain which rules match the input triple and write it as a json line
+fn explain_triple(
+    triple: TripleView,
+    rules: &Rules,
+    node_to_type: &TypeIndex,
+    out: &mut impl Write,
+) -> Result<(), std::io::Error> {
+    let explanation = explain_rules(&triple, rules, node_to_type);
+    let record = TripleExplanation {
+        triple: triple.to_string(),
+        subject: explanation.mask.is_set(&TripleMask::SUBJECT),
+        object: explanation.mask.is_set(&TripleMask::OBJECT),
+        matches: &explanation.matches,
+    };
+
+    serde_json::to_writer(&mut *out, &record)?;
+    out.write_all(b"\n")
+}
+
+/// Write one json line per input triple with the rules which matched it,
+/// the index types which caused the matches and the resulting mask.
+pub fn explain_graph(
+    _: &Logger,
+    input: &Path,
+    rules_path: &Path,
+    output: &Path,
+    index_path: &Path,
+) -> Result<(), TripsuError> {
+    let rules = io::parse_rules(rules_path)?;
+    let type_index = io::parse_index(index_path)?;
+
+    let buf_input = io::get_reader(input)?;
+    let mut buf_output = io::get_writer(output)?;
+    let mut triples = io::parse_ntriples(buf_input);
+
+    while !triples.is_end() {
+        let mut write_err = None;
+        triples.parse_step(&mut |t: TripleView| {
+            write_err = explain_triple(t, &rules, &type_index, &mut buf_output).err();
+            Result::<(), TurtleError>::Ok(())
+        })?;
+
+        if let Some(e) = write_err {
+            return Err(e.into());
+        }
+    }
+    buf_output.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::explain_graph;
+    use crate::log;
+    use serde_json::Value;
+    use std::{fs, path::Path};
+    use tempfile::tempdir;
+
+    #[test]
+    // Every input triple is explained on its own json line.
+    fn explain_nt_file() {
+        let logger = log::create_logger(true);
+
+        let dir = tempdir().unwrap();
+        let input_path = Path::new("tests/data/test.nt");
+        let rules_path = Path::new("tests/data/rules.yaml");
+        let output_path = dir.path().join("explain.jsonl");
+        let type_map_path = Path::new("tests/data/type_index.json");
+        explain_graph(&logger, input_path, rules_path, &output_path, type_map_path).unwrap();
+
+        let input = fs::read_to_string(input_path).unwrap();
+        let output = fs::read_to_string(output_path).unwrap();
+        let records: Vec<Value> = output
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(records.len(), input.lines().count());
+        // Masked parts are always explained by at least one rule
+        for r in records {
+            if r["subject"] == true || r["object"] == true {
+                assert!(!r["matches"].as_array().unwrap().is_empty());
+            }
+        }
+    }
+}
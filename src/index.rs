@@ -1,16 +1,18 @@
-use rio_api::parser::TriplesParser;
-use rio_turtle::TurtleError;
 use serde::{Deserialize, Serialize};
-use smallvec::{smallvec, SmallVec};
+use smallvec::SmallVec;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     hash::{DefaultHasher, Hash, Hasher},
-    path::Path,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
 };
 
 use crate::{
-    io,
-    rdf_types::{Triple, TripleView},
+    error::TripsuError,
+    io::{self, ErrorHandler, Processing},
+    log::Logger,
+    rdf_types::{Term, Triple, TripleView},
+    rules::Propagation,
 };
 
 /// Stores a mapping from hashed instance uri to their types.
@@ -18,9 +20,40 @@ use crate::{
 /// Each subject in map is stored as hash(subject_uri): u64
 /// and refers to its types using their vector index.
 #[derive(Serialize, Deserialize)]
+#[serde(from = "SerializedTypeIndex")]
 pub struct TypeIndex {
     pub types: Vec<String>,
     map: HashMap<u64, SmallVec<[usize; 1]>>,
+    // Vector index of each type, built once after deserialization.
+    #[serde(skip)]
+    positions: HashMap<String, usize>,
+}
+
+/// Serialized fields of a `TypeIndex`.
+#[derive(Deserialize)]
+struct SerializedTypeIndex {
+    types: Vec<String>,
+    map: HashMap<u64, SmallVec<[usize; 1]>>,
+}
+
+impl From<SerializedTypeIndex> for TypeIndex {
+    fn from(index: SerializedTypeIndex) -> Self {
+        let mut positions = HashMap::new();
+        for (idx, type_uri) in index.types.iter().enumerate() {
+            positions.entry(type_uri.clone()).or_insert(idx);
+        }
+        TypeIndex {
+            types: index.types,
+            map: index.map,
+            positions,
+        }
+    }
+}
+
+impl Default for TypeIndex {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TypeIndex {
@@ -30,48 +63,50 @@ impl TypeIndex {
         hasher.finish().to_le()
     }
 
-    pub fn from_iter<'a>(type_map: impl Iterator<Item = (&'a str, &'a str)>) -> Self {
-        let mut idx = TypeIndex::new();
-
-        type_map.for_each(|(subject_uri, type_uri)| idx.insert(subject_uri, type_uri).unwrap());
-
-        idx
-    }
-
     pub fn new() -> Self {
         TypeIndex {
             types: Vec::new(),
             map: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    // Get the vector index of a type, or add a new one.
+    fn type_idx(&mut self, type_uri: &str) -> usize {
+        if let Some(idx) = self.positions.get(type_uri) {
+            return *idx;
+        }
+        self.types.push(type_uri.to_string());
+        self.positions
+            .insert(type_uri.to_string(), self.types.len() - 1);
+        self.types.len() - 1
+    }
+
+    // Add a type to a hashed subject, if not already set.
+    fn insert_key(&mut self, key: u64, type_idx: usize) {
+        let types = self.map.entry(key).or_default();
+        if !types.contains(&type_idx) {
+            types.push(type_idx);
         }
     }
 
     // Insert input subject-type mapping into the index.
     // The index will store the hash of the subject.
-    pub fn insert(&mut self, subject_uri: &str, type_uri: &str) -> Result<(), std::io::Error> {
+    pub fn insert(&mut self, subject_uri: &str, type_uri: &str) {
         let key = self.hash(&subject_uri.to_string());
-        let type_idx: usize;
-
-        // Get type index or add a new one.
-        if self.types.contains(&type_uri.to_string()) {
-            type_idx = self.types.iter().position(|x| *x == type_uri).unwrap();
-        } else {
-            type_idx = self.types.len();
-            self.types.push(type_uri.to_string());
-        }
-        // Insert mapping into the index.
-        match self.map.get_mut(&key) {
-            Some(v) => {
-                // Push index value only when new
-                if !v.iter().any(|x| *x == type_idx) {
-                    v.push(type_idx);
-                }
-            }
-            None => {
-                self.map.insert(key, smallvec![type_idx]);
+        let type_idx = self.type_idx(type_uri);
+        self.insert_key(key, type_idx);
+    }
+
+    /// Add the mappings of another index, e.g. built over another part
+    /// of the same graph. Types are added in the order of the other index.
+    pub fn merge(&mut self, other: TypeIndex) {
+        let type_idx: Vec<usize> = other.types.iter().map(|t| self.type_idx(t)).collect();
+        for (key, types) in other.map {
+            for i in types {
+                self.insert_key(key, type_idx[i]);
             }
         }
-
-        Ok(())
     }
 
     pub fn get(&self, subject_key: &str) -> Option<Vec<&str>> {
@@ -80,35 +115,209 @@ impl TypeIndex {
             .get(&key)
             .map(|v| v.iter().map(|i| self.types[*i].as_ref()).collect())
     }
+
+    /// Give the type `as_type` to all nodes reached from instances of
+    /// `from_type` by following the predicates of the path in sequence.
+    pub fn propagate(&mut self, propagation: &Propagation, edges: &PathEdges) {
+        let Some(&from_idx) = self.positions.get(&propagation.from_type) else {
+            return;
+        };
+
+        let mut frontier: HashSet<u64> = self
+            .map
+            .iter()
+            .filter(|(_, types)| types.contains(&from_idx))
+            .map(|(key, _)| *key)
+            .collect();
+
+        for predicate in propagation.path.iter() {
+            let Some(links) = edges.links.get(predicate) else {
+                return;
+            };
+            frontier = frontier
+                .iter()
+                .filter_map(|node| links.get(node))
+                .flatten()
+                .copied()
+                .collect();
+        }
+
+        let type_idx = self.type_idx(&propagation.as_type);
+        for key in frontier {
+            self.insert_key(key, type_idx);
+        }
+    }
 }
 
-fn index_triple(t: Triple, index: &mut TypeIndex) {
-    if t.predicate.iri.as_str() == "http://www.w3.org/1999/02/22-rdf-syntax-ns#type" {
-        let r = { index.insert(&t.subject.to_string(), &t.object.to_string()) };
+impl<'a> FromIterator<(&'a str, &'a str)> for TypeIndex {
+    fn from_iter<I: IntoIterator<Item = (&'a str, &'a str)>>(type_map: I) -> Self {
+        let mut idx = TypeIndex::new();
+
+        type_map
+            .into_iter()
+            .for_each(|(subject_uri, type_uri)| idx.insert(subject_uri, type_uri));
+
+        idx
+    }
+}
+
+/// Links between nodes for the predicates of propagation paths.
+/// Nodes are stored with the same hash as in the type index.
+#[derive(Default)]
+pub struct PathEdges {
+    links: HashMap<String, HashMap<u64, Vec<u64>>>,
+}
+
+impl PathEdges {
+    /// Create empty edges for all predicates used in the propagations.
+    pub fn new(propagations: &[Propagation]) -> Self {
+        PathEdges {
+            links: propagations
+                .iter()
+                .flat_map(|p| p.path.iter())
+                .map(|pred| (pred.clone(), HashMap::new()))
+                .collect(),
+        }
+    }
+
+    /// Record the link of a triple if its predicate is on a path.
+    fn insert(&mut self, t: &Triple, index: &TypeIndex) {
+        let Some(links) = self.links.get_mut(&t.predicate.to_string()) else {
+            return;
+        };
+        let object = match &t.object {
+            Term::Literal(_) => return,
+            o => o.to_string(),
+        };
+        links
+            .entry(index.hash(&t.subject.to_string()))
+            .or_default()
+            .push(index.hash(&object));
+    }
 
-        if let Err(e) = r {
-            panic!("Error writting to out buffer: {e}");
+    /// Add the links recorded over another part of the graph.
+    fn merge(&mut self, other: PathEdges) {
+        for (predicate, links) in other.links {
+            let merged = self.links.entry(predicate).or_default();
+            for (node, targets) in links {
+                merged.entry(node).or_default().extend(targets);
+            }
         }
     }
 }
 
-pub fn create_type_index(input: &Path, output: &Path) {
-    let buf_in = io::get_reader(input);
-    let buf_out = io::get_writer(output);
-    let mut triples = io::parse_ntriples(buf_in);
+fn index_triple(t: Triple, index: &mut TypeIndex, edges: &mut PathEdges) {
+    edges.insert(&t, index);
+
+    if t.predicate.iri.as_str() == "http://www.w3.org/1999/02/22-rdf-syntax-ns#type" {
+        index.insert(&t.subject.to_string(), &t.object.to_string());
+    }
+}
+
+/// Build the type index of N-Triples read from the input. Types are
+/// propagated along the paths of the given propagations, in order.
+/// Invalid lines are handled by the error handler.
+pub fn build_type_index(
+    input: impl BufRead,
+    propagations: &[Propagation],
+    errors: &mut ErrorHandler,
+) -> Result<TypeIndex, TripsuError> {
+    let mut index = TypeIndex::new();
+    let mut edges = PathEdges::new(propagations);
+
+    io::parse_ntriples_with(input, errors, &mut |t: TripleView| {
+        index_triple(t.into(), &mut index, &mut edges);
+        Ok(())
+    })?;
+
+    propagate_types(&mut index, propagations, &edges);
+    Ok(index)
+}
+
+/// Build the type index of N-Triples read from the input on worker threads.
+/// Each chunk of whole lines is indexed separately, and the partial indexes
+/// are merged in the order of the input before propagating types.
+pub fn build_type_index_parallel(
+    input: impl BufRead + Send,
+    propagations: &[Propagation],
+    errors: &mut ErrorHandler,
+    threads: usize,
+) -> Result<TypeIndex, TripsuError> {
+    build_type_index_chunks(input, io::CHUNK_SIZE, propagations, errors, threads)
+}
+
+fn build_type_index_chunks(
+    input: impl BufRead + Send,
+    chunk_size: usize,
+    propagations: &[Propagation],
+    errors: &mut ErrorHandler,
+    threads: usize,
+) -> Result<TypeIndex, TripsuError> {
     let mut index = TypeIndex::new();
+    let mut edges = PathEdges::new(propagations);
 
-    while !triples.is_end() {
-        let _ = triples
-            .parse_step(&mut |t: TripleView| {
-                index_triple(t.into(), &mut index);
-                Result::<(), TurtleError>::Ok(())
+    // Workers collect the invalid lines of their chunk, merged with the index
+    let chunk_errors = errors.buffered();
+    io::process_chunks(
+        input,
+        chunk_size,
+        threads,
+        true,
+        |chunk, lines_before| {
+            let mut errors = chunk_errors.buffered();
+            let mut index = TypeIndex::new();
+            let mut edges = PathEdges::new(propagations);
+            io::parse_ntriples_lines(chunk, lines_before, &mut errors, &mut |t: TripleView| {
+                index_triple(t.into(), &mut index, &mut edges);
+                Ok(())
             })
-            .inspect_err(|e| {
-                panic!("Parsing error occured: {e}");
-            });
+            .map(|_| (index, edges, errors))
+        },
+        |result| {
+            let (chunk_index, chunk_edges, chunk_errors) = result?;
+            index.merge(chunk_index);
+            edges.merge(chunk_edges);
+            errors.merge(chunk_errors)
+        },
+    )?;
+
+    propagate_types(&mut index, propagations, &edges);
+    Ok(index)
+}
+
+fn propagate_types(index: &mut TypeIndex, propagations: &[Propagation], edges: &PathEdges) {
+    // Propagations are applied in order, so that a propagated
+    // type can be the starting point of a later propagation.
+    for propagation in propagations.iter() {
+        index.propagate(propagation, edges);
     }
-    let _ = serde_json::to_writer(buf_out, &index);
+}
+
+pub fn create_type_index(
+    log: &Logger,
+    input: &Path,
+    output: &Path,
+    rules_path: &Option<PathBuf>,
+    processing: &mut Processing,
+) -> Result<(), TripsuError> {
+    let propagations = match rules_path {
+        Some(path) => io::parse_rules(path)?.nodes.propagate,
+        None => Vec::new(),
+    };
+
+    let buf_in = io::get_reader(input)?;
+    let threads = processing.threads();
+    let errors = &mut processing.errors;
+    let index = match threads {
+        1 => build_type_index(buf_in, &propagations, errors)?,
+        threads => build_type_index_parallel(buf_in, &propagations, errors, threads)?,
+    };
+    errors.finish(log)?;
+
+    let mut buf_out = io::get_writer(output)?;
+    serde_json::to_writer(&mut buf_out, &index).map_err(std::io::Error::from)?;
+    buf_out.flush()?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -132,4 +341,127 @@ mod tests {
         );
         println!("{}", serde_json::to_string(&idx).unwrap());
     }
+
+    #[test]
+    // Merged indexes keep the types of both, without duplicates.
+    fn index_merge() {
+        let mut idx = TypeIndex::from_iter(vec![
+            ("<urn:Alice>", "<urn:Person>"),
+            ("<urn:ACME>", "<urn:Organization>"),
+        ]);
+        idx.merge(TypeIndex::from_iter(vec![
+            ("<urn:Alice>", "<urn:Employee>"),
+            ("<urn:Alice>", "<urn:Person>"),
+            ("<urn:Bob>", "<urn:Person>"),
+        ]));
+
+        assert_eq!(
+            idx.types,
+            vec!["<urn:Person>", "<urn:Organization>", "<urn:Employee>"]
+        );
+        assert_eq!(
+            idx.get("<urn:Alice>").unwrap(),
+            vec!["<urn:Person>", "<urn:Employee>"]
+        );
+        assert_eq!(idx.get("<urn:Bob>").unwrap(), vec!["<urn:Person>"]);
+    }
+
+    #[test]
+    // Types are looked up after deserialization.
+    fn index_deserialized() {
+        let idx = TypeIndex::from_iter(vec![("<urn:Alice>", "<urn:Person>")]);
+        let mut idx: TypeIndex =
+            serde_json::from_str(&serde_json::to_string(&idx).unwrap()).unwrap();
+        idx.insert("<urn:Bob>", "<urn:Person>");
+
+        assert_eq!(idx.types, vec!["<urn:Person>"]);
+        assert_eq!(idx.get("<urn:Bob>").unwrap(), vec!["<urn:Person>"]);
+
+        // A duplicate type refers to its first position
+        let mut idx: TypeIndex =
+            serde_json::from_str(r#"{"types":["<urn:Person>","<urn:Person>"],"map":{}}"#).unwrap();
+        idx.insert("<urn:Bob>", "<urn:Person>");
+
+        assert_eq!(idx.types.len(), 2);
+        assert_eq!(idx.get("<urn:Bob>").unwrap(), vec!["<urn:Person>"]);
+    }
+
+    #[test]
+    // Test the propagation of types along a property path.
+    fn propagate_path() {
+        let input = r#"
+            <urn:Alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Person> .
+            <urn:Alice> <urn:hasAddress> _:a1 .
+            _:a1 <urn:hasGeo> <urn:geo1> .
+            <urn:ACME> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Organization> .
+            <urn:ACME> <urn:hasAddress> _:a2 .
+            _:a2 <urn:hasGeo> <urn:geo2> .
+        "#;
+        let propagations = vec![
+            Propagation {
+                from_type: "<urn:Person>".to_string(),
+                path: vec!["<urn:hasAddress>".to_string()],
+                as_type: "<urn:PersonalAddress>".to_string(),
+            },
+            Propagation {
+                from_type: "<urn:PersonalAddress>".to_string(),
+                path: vec!["<urn:hasGeo>".to_string()],
+                as_type: "<urn:PersonalGeo>".to_string(),
+            },
+        ];
+        let index = build_type_index(
+            input.as_bytes(),
+            &propagations,
+            &mut ErrorHandler::default(),
+        )
+        .unwrap();
+
+        assert_eq!(index.get("_:a1").unwrap(), vec!["<urn:PersonalAddress>"]);
+        assert_eq!(index.get("<urn:geo1>").unwrap(), vec!["<urn:PersonalGeo>"]);
+        assert!(index.get("_:a2").is_none());
+        assert!(index.get("<urn:geo2>").is_none());
+    }
+
+    #[test]
+    // Partial indexes of chunks give the same index as a single pass,
+    // with types propagated along paths crossing chunks.
+    fn index_chunks() {
+        let input = r#"<urn:Alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Person> .
+<urn:Alice> <urn:hasAddress> _:a1 .
+<urn:ACME> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Organization> .
+<urn:Alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Employee> .
+_:a1 <urn:hasGeo> <urn:geo1> .
+<urn:Bob> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Person> .
+"#;
+        let propagations = vec![Propagation {
+            from_type: "<urn:Person>".to_string(),
+            path: vec!["<urn:hasAddress>".to_string(), "<urn:hasGeo>".to_string()],
+            as_type: "<urn:PersonalGeo>".to_string(),
+        }];
+        let index = |threads| {
+            build_type_index_chunks(
+                input.as_bytes(),
+                40,
+                &propagations,
+                &mut ErrorHandler::default(),
+                threads,
+            )
+            .unwrap()
+        };
+        let single = build_type_index(
+            input.as_bytes(),
+            &propagations,
+            &mut ErrorHandler::default(),
+        )
+        .unwrap();
+
+        for threads in [1, 3] {
+            let chunked = index(threads);
+            assert_eq!(chunked.types, single.types);
+            for node in ["<urn:Alice>", "<urn:ACME>", "<urn:Bob>", "<urn:geo1>"] {
+                assert_eq!(chunked.get(node), single.get(node));
+            }
+        }
+        assert_eq!(single.get("<urn:geo1>").unwrap(), vec!["<urn:PersonalGeo>"]);
+    }
 }
@@ -1,10 +1,24 @@
-use crate::{index::TypeIndex, rules::Rules};
-use rio_turtle::NTriplesParser;
+use crate::{
+    error::TripsuError,
+    index::TypeIndex,
+    log::{info, warn, Logger},
+    rdf_types::TripleView,
+    rules::{suggest_field, Rules},
+};
+use rio_api::parser::TriplesParser;
+use rio_turtle::{NTriplesParser, TurtleError, TurtleParser};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yml::{Mapping, Value};
 use std::{
-    fs::File,
-    io::{self, stdin, stdout, BufRead, BufReader, BufWriter, Read},
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{self, stdin, stdout, BufRead, BufReader, BufWriter, Read, Write},
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
 };
+use tempfile::NamedTempFile;
 
 use io_enum::{BufRead, Read, Write};
 
@@ -21,19 +35,37 @@ pub enum Writer {
 }
 
 /// Get a reader based on input path, either from stdin or a file.
-pub fn get_reader(path: &Path) -> Reader {
-    return match path.to_str().unwrap() {
-        "-" => Reader::Stdio(BufReader::new(stdin())),
-        path => Reader::File(BufReader::new(File::open(path).unwrap())),
+pub fn get_reader(path: &Path) -> Result<Reader, TripsuError> {
+    Ok(match path.to_str() {
+        Some("-") => Reader::Stdio(BufReader::new(stdin())),
+        _ => Reader::File(BufReader::new(File::open(path).map_err(|e| {
+            TripsuError::Io(format!("Cannot open input file '{:?}': '{}'.", path, e))
+        })?)),
+    })
+}
+
+/// Copy stdin to a temporary file, which can be read several times.
+/// The file is removed when dropped.
+pub fn spill_stdin(mut stdin: impl Read) -> Result<NamedTempFile, TripsuError> {
+    let mut spill = |file: &mut NamedTempFile| -> io::Result<()> {
+        io::copy(&mut stdin, file)?;
+        file.flush()
     };
+    let mut file = NamedTempFile::new()?;
+    spill(&mut file).map_err(|e| {
+        TripsuError::Io(format!("Cannot spill stdin to a temporary file: '{}'.", e))
+    })?;
+    Ok(file)
 }
 
 /// Get a writer based on input path, either to stdout or a file.
-pub fn get_writer(path: &Path) -> Writer {
-    return match path.to_str().unwrap() {
-        "-" => Writer::Stdio(BufWriter::new(stdout())),
-        path => Writer::File(BufWriter::new(File::create(path).unwrap())),
-    };
+pub fn get_writer(path: &Path) -> Result<Writer, TripsuError> {
+    Ok(match path.to_str() {
+        Some("-") => Writer::Stdio(BufWriter::new(stdout())),
+        _ => Writer::File(BufWriter::new(File::create(path).map_err(|e| {
+            TripsuError::Io(format!("Cannot create output file '{:?}': '{}'.", path, e))
+        })?)),
+    })
 }
 
 /// Parse RDF triples.
@@ -42,44 +74,605 @@ pub fn parse_ntriples(reader: impl BufRead) -> NTriplesParser<impl BufRead> {
     NTriplesParser::new(reader)
 }
 
-/// Parse yaml configuration file.
-pub fn parse_rules(path: &Path) -> Rules {
-    let rules: Rules = match File::open(path) {
-        Ok(file) => serde_yml::from_reader(file).expect("Error parsing rules file."),
-        Err(e) => panic!("Cannot open rules file '{:?}': '{}'.", path, e),
+/// Handling of input lines which cannot be parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnError {
+    /// Skip invalid lines.
+    Skip,
+    /// Write invalid lines with their line number to a quarantine file.
+    Quarantine,
+    /// Stop at the first invalid line.
+    #[default]
+    Fail,
+}
+
+/// Handles invalid input lines according to the selected mode,
+/// and counts them.
+#[derive(Default)]
+pub struct ErrorHandler {
+    mode: OnError,
+    quarantine: Option<Writer>,
+    /// Quarantined lines of a buffered handler.
+    buffer: Option<Vec<u8>>,
+    invalid: usize,
+}
+
+impl ErrorHandler {
+    /// Create a handler, the quarantine file is required in quarantine mode.
+    pub fn new(mode: OnError, quarantine: Option<&Path>) -> Result<Self, TripsuError> {
+        let quarantine = match (mode, quarantine) {
+            (OnError::Quarantine, Some(path)) => Some(get_writer(path)?),
+            (OnError::Quarantine, None) => {
+                return Err(TripsuError::Usage(
+                    "A quarantine file is required to quarantine invalid lines.".to_string(),
+                ))
+            }
+            _ => None,
+        };
+        Ok(ErrorHandler {
+            mode,
+            quarantine,
+            buffer: None,
+            invalid: 0,
+        })
+    }
+
+    /// Number of invalid lines skipped or quarantined.
+    pub fn invalid(&self) -> usize {
+        self.invalid
+    }
+
+    /// Create a handler with the same mode, keeping quarantined lines
+    /// in memory until merged back with `merge`.
+    pub(crate) fn buffered(&self) -> Self {
+        ErrorHandler {
+            mode: self.mode,
+            quarantine: None,
+            buffer: (self.mode == OnError::Quarantine).then(Vec::new),
+            invalid: 0,
+        }
+    }
+
+    /// Create a handler skipping the invalid lines instead of quarantining
+    /// them, for passes over an input whose invalid lines are reported once.
+    pub(crate) fn skipping(&self) -> Self {
+        let mode = match self.mode {
+            OnError::Fail => OnError::Fail,
+            _ => OnError::Skip,
+        };
+        ErrorHandler {
+            mode,
+            quarantine: None,
+            buffer: None,
+            invalid: 0,
+        }
+    }
+
+    /// Add the invalid lines of a buffered handler.
+    pub(crate) fn merge(&mut self, other: ErrorHandler) -> Result<(), TripsuError> {
+        self.invalid += other.invalid;
+        if let (Some(out), Some(lines)) = (self.output(), other.buffer) {
+            out.write_all(&lines)?;
+        }
+        Ok(())
+    }
+
+    /// Where quarantined lines are written.
+    fn output(&mut self) -> Option<&mut dyn Write> {
+        match (&mut self.quarantine, &mut self.buffer) {
+            (Some(out), _) => Some(out),
+            (None, Some(buffer)) => Some(buffer),
+            (None, None) => None,
+        }
+    }
+
+    fn handle(
+        &mut self,
+        line_number: usize,
+        line: &[u8],
+        error: TurtleError,
+    ) -> Result<(), TripsuError> {
+        // Lines are parsed alone, so that positions in the error are relative to the line
+        let message = error.to_string();
+        let message = message
+            .rsplit_once(" on line ")
+            .map_or(message.as_str(), |(m, _)| m);
+
+        self.invalid += 1;
+        match (self.mode, self.output()) {
+            (OnError::Fail, _) => Err(TripsuError::Parse(format!(
+                "{message} on line {line_number}"
+            ))),
+            (OnError::Quarantine, Some(out)) => {
+                writeln!(out, "# line {line_number}: {message}")?;
+                out.write_all(line.strip_suffix(b"\n").unwrap_or(line))?;
+                out.write_all(b"\n")?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Flush the quarantine file and log the number of invalid lines.
+    pub fn finish(&mut self, log: &Logger) -> Result<(), TripsuError> {
+        if let Some(out) = &mut self.quarantine {
+            out.flush()?;
+        }
+        let action = match self.mode {
+            // The first invalid line is an error
+            OnError::Fail => return Ok(()),
+            OnError::Skip => "skipped",
+            OnError::Quarantine => "quarantined",
+        };
+        if self.invalid > 0 {
+            warn!(log, "{} invalid line(s) {action}", self.invalid);
+        } else {
+            info!(log, "0 invalid line(s) {action}");
+        }
+        Ok(())
+    }
+}
+
+/// Parse N-Triples, calling `on_triple` on each triple.
+/// Invalid lines are handled by the error handler, except in fail mode
+/// where the input is streamed to the parser instead of line by line.
+pub fn parse_ntriples_with(
+    input: impl BufRead,
+    errors: &mut ErrorHandler,
+    on_triple: &mut impl FnMut(TripleView) -> Result<(), TripsuError>,
+) -> Result<(), TripsuError> {
+    let mut triple_err = None;
+
+    if errors.mode == OnError::Fail {
+        let mut triples = parse_ntriples(input);
+        while !triples.is_end() {
+            triples.parse_step(&mut |t: TripleView| {
+                triple_err = on_triple(t).err();
+                Result::<(), TurtleError>::Ok(())
+            })?;
+
+            if let Some(e) = triple_err {
+                return Err(e);
+            }
+        }
+        return Ok(());
+    }
+
+    parse_ntriples_lines(input, 0, errors, on_triple)
+}
+
+/// Parse N-Triples line by line, calling `on_triple` on each triple.
+/// Invalid lines are handled by the error handler, with line numbers
+/// following the given number of lines before the input.
+pub fn parse_ntriples_lines(
+    mut input: impl BufRead,
+    lines_before: usize,
+    errors: &mut ErrorHandler,
+    on_triple: &mut impl FnMut(TripleView) -> Result<(), TripsuError>,
+) -> Result<(), TripsuError> {
+    let mut triple_err = None;
+    let mut line = Vec::new();
+    let mut line_number = lines_before;
+    loop {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        line_number += 1;
+
+        let mut triples = parse_ntriples(line.as_slice());
+        while !triples.is_end() {
+            let r = triples.parse_step(&mut |t: TripleView| {
+                triple_err = on_triple(t).err();
+                Result::<(), TurtleError>::Ok(())
+            });
+
+            if let Some(e) = triple_err {
+                return Err(e);
+            }
+            if let Err(e) = r {
+                errors.handle(line_number, &line, e)?;
+                break;
+            }
+        }
+    }
+}
+
+/// Size of the chunks of input processed by worker threads.
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+/// Read a chunk of about `size` bytes of the input, completed up to the
+/// end of its last line. Returns `None` at the end of the input.
+pub fn read_chunk(input: &mut impl BufRead, size: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut chunk = Vec::with_capacity(size);
+    while chunk.len() < size {
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        let n = buf.len().min(size - chunk.len());
+        chunk.extend_from_slice(&buf[..n]);
+        input.consume(n);
+    }
+    if chunk.last().is_some_and(|b| *b != b'\n') {
+        input.read_until(b'\n', &mut chunk)?;
+    }
+
+    Ok((!chunk.is_empty()).then_some(chunk))
+}
+
+/// Processing of the input triples.
+pub struct Processing {
+    /// Handling of invalid lines.
+    pub errors: ErrorHandler,
+    /// Number of worker threads, 0 for all available cores.
+    /// With a single thread, the input is processed in the calling thread.
+    pub threads: usize,
+    /// Keep the order of the input in the output.
+    pub ordered: bool,
+}
+
+impl Default for Processing {
+    /// Sequential processing, failing on invalid lines.
+    fn default() -> Self {
+        Processing {
+            errors: ErrorHandler::default(),
+            threads: 1,
+            ordered: true,
+        }
+    }
+}
+
+impl Processing {
+    /// Number of worker threads to use.
+    pub fn threads(&self) -> usize {
+        match self.threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        }
+    }
+}
+
+/// Process the input in line-aligned chunks of about `chunk_size` bytes
+/// on worker threads. `work` receives each chunk with the number of lines before it, and
+/// `on_result` the results of `work`, in the order of the input when
+/// `ordered`, otherwise as soon as they are ready. At most `2 * threads`
+/// chunks are read ahead of the results handled, so that a slow chunk
+/// does not make the results waiting for it grow without bound.
+/// A panic in `work` stops the processing and is propagated to the caller.
+pub fn process_chunks<T: Send>(
+    mut input: impl BufRead + Send,
+    chunk_size: usize,
+    threads: usize,
+    ordered: bool,
+    work: impl Fn(&[u8], usize) -> T + Sync,
+    mut on_result: impl FnMut(T) -> Result<(), TripsuError>,
+) -> Result<(), TripsuError> {
+    thread::scope(|scope| {
+        let (chunk_tx, chunk_rx) = mpsc::sync_channel::<(usize, usize, Vec<u8>)>(threads);
+        let (result_tx, result_rx) = mpsc::sync_channel::<(usize, thread::Result<T>)>(threads);
+        let chunk_rx = Arc::new(Mutex::new(chunk_rx));
+        let work = &work;
+
+        // Each chunk read takes a credit, returned once its result is handled
+        let window = 2 * threads;
+        let (credit_tx, credit_rx) = mpsc::sync_channel::<()>(window);
+        for _ in 0..window {
+            credit_tx.send(()).unwrap();
+        }
+
+        let reader = scope.spawn(move || -> io::Result<()> {
+            let mut lines_before = 0;
+            let mut seq = 0;
+            // The handling of results is gone if processing stopped on an error
+            while credit_rx.recv().is_ok() {
+                let Some(chunk) = read_chunk(&mut input, chunk_size)? else {
+                    break;
+                };
+                let lines = chunk.iter().filter(|b| **b == b'\n').count();
+                // Workers are gone if processing stopped on an error
+                if chunk_tx.send((seq, lines_before, chunk)).is_err() {
+                    break;
+                }
+                seq += 1;
+                lines_before += lines;
+            }
+            Ok(())
+        });
+
+        for _ in 0..threads {
+            let chunk_rx = Arc::clone(&chunk_rx);
+            let result_tx = result_tx.clone();
+            scope.spawn(move || loop {
+                let next = chunk_rx.lock().unwrap().recv();
+                let Ok((seq, lines_before, chunk)) = next else {
+                    break;
+                };
+                let result = panic::catch_unwind(AssertUnwindSafe(|| work(&chunk, lines_before)));
+                let panicked = result.is_err();
+                if result_tx.send((seq, result)).is_err() || panicked {
+                    break;
+                }
+            });
+        }
+        drop(result_tx);
+
+        // Results are kept until the results of all previous chunks are handled
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        let mut panicked = None;
+        for (seq, result) in result_rx {
+            // The panicking chunk never comes, so results cannot be handled further
+            let result = match result {
+                Ok(result) => result,
+                Err(payload) => {
+                    panicked = Some(payload);
+                    break;
+                }
+            };
+            if !ordered {
+                on_result(result)?;
+                let _ = credit_tx.send(());
+                continue;
+            }
+            pending.insert(seq, result);
+            while let Some(result) = pending.remove(&next) {
+                on_result(result)?;
+                let _ = credit_tx.send(());
+                next += 1;
+            }
+        }
+        if let Some(payload) = panicked {
+            // Stop the reader and the other workers before propagating the panic
+            drop((credit_tx, chunk_rx));
+            panic::resume_unwind(payload);
+        }
+
+        reader.join().expect("Reader thread panicked")?;
+        Ok(())
+    })
+}
+
+/// Formats of rules files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RulesFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl RulesFormat {
+    /// Detect the format from the file extension, defaulting to yaml.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => RulesFormat::Json,
+            Some("toml") => RulesFormat::Toml,
+            _ => RulesFormat::Yaml,
+        }
+    }
+
+    /// Deserialize a document in this format.
+    pub fn deserialize<T: DeserializeOwned>(&self, source: &str) -> Result<T, anyhow::Error> {
+        Ok(match self {
+            RulesFormat::Yaml => serde_yml::from_str(source)?,
+            RulesFormat::Json => serde_json::from_str(source)?,
+            RulesFormat::Toml => toml::from_str(source)?,
+        })
+    }
+
+    /// Serialize a document in this format.
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String, anyhow::Error> {
+        Ok(match self {
+            RulesFormat::Yaml => serde_yml::to_string(value)?,
+            RulesFormat::Json => serde_json::to_string_pretty(value)? + "\n",
+            RulesFormat::Toml => toml::to_string_pretty(value)?,
+        })
+    }
+}
+
+/// Parse configuration file, merged with the rule files it includes.
+/// The format of each file is detected from its extension.
+/// cURIes are expanded once on the merged rules, with the declared
+/// prefixes completed by the imported ones.
+pub fn parse_rules(path: &Path) -> Result<Rules, TripsuError> {
+    let value = load_rules_value(path, &mut Vec::new())?;
+    let mut rules: Rules = serde_yml::from_value(value).map_err(|e| {
+        TripsuError::Rules(format!(
+            "Error parsing rules file: {}",
+            suggest_field(&e.to_string())
+        ))
+    })?;
+    let mut namespaces = HashMap::new();
+    for prefix_file in rules.prefix_files() {
+        namespaces.extend(parse_turtle_prefixes(prefix_file)?);
+    }
+    rules
+        .import_prefixes(namespaces)
+        .map_err(|e| TripsuError::Rules(format!("Error importing rules prefixes. {}", e)))?;
+    rules
+        .expand_rules_curie()
+        .map_err(|e| TripsuError::Rules(format!("Error expanding rules curie. {}", e)))
+}
+
+/// Read a rules file and merge it over the files it includes.
+/// Includes are resolved relative to the including file and merged in order.
+/// The stack holds the files being loaded, to detect cyclic includes.
+fn load_rules_value(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, TripsuError> {
+    let file = path.canonicalize().map_err(|e| {
+        TripsuError::Rules(format!("Cannot open rules file '{:?}': '{}'.", path, e))
+    })?;
+    if stack.contains(&file) {
+        let chain: Vec<_> = stack.iter().chain([&file]).map(|p| p.display()).collect();
+        return Err(TripsuError::Rules(format!(
+            "Cyclic include of rules files: {}",
+            chain
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        )));
+    }
+
+    let source = fs::read_to_string(&file).map_err(|e| {
+        TripsuError::Rules(format!("Cannot open rules file '{:?}': '{}'.", path, e))
+    })?;
+    stack.push(file.clone());
+    let value = load_rules_source(&source, path, &file, stack);
+    stack.pop();
+    value
+}
+
+/// Parse the source of the rules file at the given path and merge it over
+/// the files it includes. Paths in the rules are relative to the directory
+/// of `file`, the canonical path of the rules file.
+pub(crate) fn load_rules_source(
+    source: &str,
+    path: &Path,
+    file: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Value, TripsuError> {
+    let mut value: Value = RulesFormat::from_path(path)
+        .deserialize(source)
+        .map_err(|e| TripsuError::Rules(format!("Error parsing rules file '{:?}': {}", path, e)))?;
+
+    // Prefix files are relative to the file declaring them.
+    let dir = file.parent().unwrap_or(Path::new("."));
+    if let Some(Value::Sequence(files)) = value
+        .as_mapping_mut()
+        .and_then(|m| m.get_mut("prefixes_from"))
+    {
+        for prefix_file in files.iter_mut() {
+            if let Value::String(prefix_file) = prefix_file {
+                *prefix_file = dir.join(&*prefix_file).to_string_lossy().into_owned();
+            }
+        }
+    }
+
+    let Some(includes) = value.as_mapping_mut().and_then(|m| m.remove("include")) else {
+        return Ok(value);
     };
-    match rules.expand_rules_curie() {
-        Ok(expanded_rules) => expanded_rules,
-        Err(e) => panic!("Error expanding rules curie. {}", e),
+    let includes: Vec<PathBuf> = serde_yml::from_value(includes).map_err(|e| {
+        TripsuError::Rules(format!("Invalid include in rules file '{:?}': {}", path, e))
+    })?;
+
+    let mut merged = Value::Mapping(Mapping::new());
+    for include in includes {
+        merge_yaml(&mut merged, load_rules_value(&dir.join(include), stack)?);
     }
+    merge_yaml(&mut merged, value);
+
+    Ok(merged)
 }
 
-/// Parse yaml type index
-pub fn parse_index(path: &Path) -> TypeIndex {
-    match File::open(path) {
-        Ok(file) => serde_json::from_reader(file).expect("Error parsing index file."),
-        Err(e) => panic!("Cannot open index file '{:?}': '{}'.", path, e),
+/// Merge yaml values. Mappings are merged key by key, sequences are
+/// concatenated without duplicates and other values are overridden.
+/// Empty values do not override anything.
+fn merge_yaml(base: &mut Value, over: Value) {
+    match (base, over) {
+        (_, Value::Null) => {}
+        (Value::Mapping(base), Value::Mapping(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_yaml(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(over)) => {
+            for value in over {
+                if !base.contains(&value) {
+                    base.push(value);
+                }
+            }
+        }
+        (base, over) => *base = over,
     }
 }
 
-/// Read all file content as bytes.
-pub fn read_bytes(path: &PathBuf) -> Vec<u8> {
-    let mut file = File::open(path).expect("Error opening key file.");
+/// Read the `@prefix` declarations of a turtle file.
+pub fn parse_turtle_prefixes(path: &Path) -> Result<HashMap<String, String>, TripsuError> {
+    let file = File::open(path).map_err(|e| {
+        TripsuError::Rules(format!("Cannot open prefix file '{:?}': '{}'.", path, e))
+    })?;
+    let mut parser = TurtleParser::new(BufReader::new(file), None);
+    parser
+        .parse_all(&mut |_| Result::<(), TurtleError>::Ok(()))
+        .map_err(|e| {
+            TripsuError::Rules(format!("Error parsing prefix file '{:?}': {}", path, e))
+        })?;
+
+    Ok(parser.prefixes().clone())
+}
+
+/// Convert a rules file to another format, keeping includes and cURIes.
+/// Without a target format, it is detected from the output extension.
+pub fn convert_rules(
+    input: &Path,
+    output: &Path,
+    to: Option<RulesFormat>,
+) -> Result<(), TripsuError> {
+    let source = fs::read_to_string(input).map_err(|e| {
+        TripsuError::Rules(format!("Cannot open rules file '{:?}': '{}'.", input, e))
+    })?;
+    let rules: Rules = RulesFormat::from_path(input)
+        .deserialize(&source)
+        .map_err(|e| {
+            TripsuError::Rules(format!(
+                "Error parsing rules file: {}",
+                suggest_field(&e.to_string())
+            ))
+        })?;
+
+    let to = to.unwrap_or_else(|| RulesFormat::from_path(output));
+    let converted = to
+        .serialize(&rules)
+        .map_err(|e| TripsuError::Rules(format!("Cannot convert rules: {e}")))?;
+    let mut buf_output = get_writer(output)?;
+    buf_output.write_all(converted.as_bytes())?;
+
+    Ok(())
+}
+
+/// Parse json type index
+pub fn parse_index(path: &Path) -> Result<TypeIndex, TripsuError> {
+    let file = File::open(path).map_err(|e| {
+        TripsuError::Index(format!("Cannot open index file '{:?}': '{}'.", path, e))
+    })?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| TripsuError::Index(format!("Error parsing index file '{:?}': {}", path, e)))
+}
+
+/// Read all content of the secret key file as bytes.
+pub fn read_bytes(path: &PathBuf) -> Result<Vec<u8>, TripsuError> {
     let mut data = Vec::new();
-    file.read_to_end(&mut data)
-        .expect("Error reading key file.");
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut data))
+        .map_err(|e| TripsuError::Key(format!("Cannot read key file '{:?}': '{}'.", path, e)))?;
 
-    data
+    Ok(data)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_ntriples, parse_rules};
+    use super::{
+        convert_rules, parse_ntriples, parse_ntriples_with, parse_rules, process_chunks,
+        ErrorHandler, OnError, TripsuError,
+    };
+    use crate::log;
     use rio_api::parser::TriplesParser;
+    use rstest::rstest;
     use std::{
+        fs,
         io::{BufRead, BufReader},
+        panic,
         path::Path,
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
     };
+    use tempfile::tempdir;
 
     #[test]
     // Test the parsing of a triple.
@@ -96,10 +689,304 @@ mod tests {
             })
             .expect("Error parsing triple");
     }
+    #[rstest]
+    #[case(OnError::Skip, Some(2), "")]
+    #[case(OnError::Quarantine, Some(2), "# line 2: unexpected character 'n'\nnot a triple\n# line 4: premature end of file\n<urn:c> <urn:p>\n")]
+    #[case(OnError::Fail, None, "")]
+    // Invalid lines are skipped or quarantined, and parsing resumes at the next line.
+    fn lenient_parsing(
+        #[case] mode: OnError,
+        #[case] invalid: Option<usize>,
+        #[case] quarantined: &str,
+    ) {
+        let input =
+            "<urn:a> <urn:p> \"a\" .\nnot a triple\n<urn:b> <urn:p> \"b\" .\n<urn:c> <urn:p>";
+        let dir = tempdir().unwrap();
+        let quarantine = dir.path().join("quarantine.nt");
+        let mut errors = ErrorHandler::new(mode, Some(&quarantine)).unwrap();
+
+        let mut subjects = Vec::new();
+        let r = parse_ntriples_with(input.as_bytes(), &mut errors, &mut |t| {
+            subjects.push(t.subject.to_string());
+            Ok(())
+        });
+        errors.finish(&log::create_logger(true)).unwrap();
+
+        match invalid {
+            Some(invalid) => {
+                r.unwrap();
+                assert_eq!(subjects, vec!["<urn:a>", "<urn:b>"]);
+                assert_eq!(errors.invalid(), invalid);
+            }
+            None => assert!(matches!(r, Err(TripsuError::Parse(_)))),
+        }
+        assert_eq!(
+            fs::read_to_string(&quarantine).unwrap_or_default(),
+            quarantined
+        );
+    }
+
+    #[test]
+    // Quarantining without a quarantine file is a usage error.
+    fn quarantine_without_file() {
+        let r = ErrorHandler::new(OnError::Quarantine, None);
+        assert!(matches!(r, Err(TripsuError::Usage(_))));
+    }
+
+    #[rstest]
+    #[case(true)]
+    #[case(false)]
+    // Chunks are made of whole lines, and results come in the input order if ordered.
+    fn chunk_processing(#[case] ordered: bool) {
+        let input = (0..100)
+            .map(|i| format!("line {i}\n"))
+            .collect::<Vec<_>>()
+            .concat();
+        let mut results = Vec::new();
+        process_chunks(
+            input.as_bytes(),
+            20,
+            4,
+            ordered,
+            |chunk, lines_before| {
+                let lines = String::from_utf8(chunk.to_vec()).unwrap();
+                (lines_before, lines)
+            },
+            |result| {
+                results.push(result);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        if !ordered {
+            results.sort();
+        }
+        assert!(results.len() > 1);
+        assert_eq!(
+            results.iter().map(|(_, l)| l.as_str()).collect::<String>(),
+            input
+        );
+        for (lines_before, lines) in results {
+            assert!(lines.ends_with('\n'));
+            assert!(lines.starts_with(&format!("line {lines_before}\n")));
+        }
+    }
+
+    #[rstest]
+    #[case(1, true)]
+    #[case(4, true)]
+    #[case(4, false)]
+    // A panic while processing a chunk is propagated instead of blocking.
+    fn chunk_processing_panic(#[case] threads: usize, #[case] ordered: bool) {
+        let input = "line\n".repeat(100);
+        let r = panic::catch_unwind(|| {
+            process_chunks(
+                input.as_bytes(),
+                5,
+                threads,
+                ordered,
+                |_, lines_before| {
+                    if lines_before == 10 {
+                        panic!("chunk failed");
+                    }
+                },
+                |_| Ok(()),
+            )
+        });
+
+        assert!(r.is_err());
+    }
+
+    #[test]
+    // Chunks are not read far ahead of a slow chunk whose results are awaited.
+    fn chunk_processing_window() {
+        let input = "line\n".repeat(100);
+        let started = AtomicUsize::new(0);
+        let mut read_ahead = 0;
+        process_chunks(
+            input.as_bytes(),
+            5,
+            2,
+            true,
+            |_, lines_before| {
+                started.fetch_add(1, Ordering::SeqCst);
+                if lines_before == 0 {
+                    thread::sleep(Duration::from_millis(100));
+                    return Some(started.load(Ordering::SeqCst));
+                }
+                None
+            },
+            |result| {
+                read_ahead = read_ahead.max(result.unwrap_or_default());
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert!(read_ahead > 1);
+        assert!(read_ahead <= 4);
+    }
+
     // Test the parsing of a config file.
     #[test]
     fn rules_parsing() {
         let config_path = Path::new("tests/data/rules.yaml");
-        parse_rules(config_path);
+        parse_rules(config_path).unwrap();
+    }
+
+    #[test]
+    // Included rules are merged before the including file.
+    fn rules_include() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("base")).unwrap();
+        fs::write(
+            dir.path().join("base/base.yaml"),
+            r#"
+prefixes:
+  ex: "<http://example.org/>"
+nodes:
+  invert: true
+  of_type: ["ex:Person"]
+objects:
+  on_predicate: ["ex:hasName"]
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("rules.yaml"),
+            r#"
+include: ["base/base.yaml"]
+prefixes:
+  ex: "<http://example.com/>"
+nodes:
+  invert: false
+  of_type: ["ex:Person", "<urn:Robot>"]
+"#,
+        )
+        .unwrap();
+
+        let rules = parse_rules(&dir.path().join("rules.yaml")).unwrap();
+        let mut of_type: Vec<_> = rules
+            .uri_entries()
+            .iter()
+            .map(|e| e.uri.to_string())
+            .collect();
+        of_type.sort();
+
+        assert!(!rules.nodes.invert);
+        // cURIes are expanded with the merged prefixes
+        assert_eq!(
+            of_type,
+            vec![
+                "<http://example.com/Person>",
+                "<http://example.com/hasName>",
+                "<urn:Robot>"
+            ]
+        );
+    }
+
+    #[test]
+    // Well-known and turtle prefixes complete the declared ones.
+    fn rules_prefixes() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("rules")).unwrap();
+        fs::write(
+            dir.path().join("prefixes.ttl"),
+            r#"
+@prefix ex: <http://example.org/> .
+@prefix foaf: <http://example.org/foaf/> .
+ex:Alice a foaf:Person .
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("rules/rules.yaml"),
+            r#"
+use_prefixes: [foaf, schema]
+prefixes_from: [../prefixes.ttl]
+prefixes:
+  schema: "<https://schema.org/>"
+nodes:
+  of_type: ["ex:Robot", "foaf:Person", "schema:Person"]
+"#,
+        )
+        .unwrap();
+
+        let rules = parse_rules(&dir.path().join("rules/rules.yaml")).unwrap();
+        let mut of_type: Vec<_> = rules
+            .uri_entries()
+            .iter()
+            .map(|e| e.uri.to_string())
+            .collect();
+        of_type.sort();
+
+        // Turtle prefixes override well-known ones, declared ones override both
+        assert_eq!(
+            of_type,
+            vec![
+                "<http://example.org/Robot>",
+                "<http://example.org/foaf/Person>",
+                "<https://schema.org/Person>"
+            ]
+        );
+    }
+
+    #[test]
+    fn rules_include_cycle() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.yaml"), "include: [b.yaml]\n").unwrap();
+        fs::write(dir.path().join("b.yaml"), "include: [a.yaml]\n").unwrap();
+
+        let err = parse_rules(&dir.path().join("a.yaml")).unwrap_err();
+        assert!(
+            matches!(&err, TripsuError::Rules(msg) if msg.starts_with("Cyclic include of rules files")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    // Rules converted to json and toml are loaded as the original yaml.
+    fn rules_formats() {
+        let dir = tempdir().unwrap();
+        let yaml_path = Path::new("tests/data/rules.yaml");
+        let entries = |path: &Path| {
+            let mut entries: Vec<_> = parse_rules(path)
+                .unwrap()
+                .uri_entries()
+                .iter()
+                .map(|e| format!("{} {}", e.field, e.uri))
+                .collect();
+            entries.sort();
+            entries
+        };
+
+        for ext in ["json", "toml"] {
+            let path = dir.path().join(format!("rules.{ext}"));
+            convert_rules(yaml_path, &path, None).unwrap();
+            assert_eq!(entries(&path), entries(yaml_path));
+        }
+    }
+
+    #[test]
+    // Prefixes survive conversions from and to toml.
+    fn rules_formats_prefixes() {
+        let dir = tempdir().unwrap();
+        let yaml_path = dir.path().join("rules.yaml");
+        fs::write(
+            &yaml_path,
+            "prefixes:\n  ex: \"<http://example.org/>\"\nnodes:\n  of_type: [\"ex:Person\"]\n",
+        )
+        .unwrap();
+        let toml_path = dir.path().join("rules.toml");
+        let json_path = dir.path().join("rules.json");
+        convert_rules(&yaml_path, &toml_path, None).unwrap();
+        convert_rules(&toml_path, &json_path, None).unwrap();
+
+        for path in [&yaml_path, &toml_path, &json_path] {
+            let rules = parse_rules(path).unwrap();
+            let of_type: Vec<_> = rules.uri_entries().iter().map(|e| e.uri).collect();
+            assert_eq!(of_type, vec!["<http://example.org/Person>"], "{path:?}");
+        }
     }
 }
@@ -0,0 +1,94 @@
+//! Pseudonymization of URIs and values in RDF graphs.
+//!
+//! Graphs are pseudonymized in two passes: a [`TypeIndex`] mapping nodes
+//! to their `rdf:type` is first built from the graph, then a
+//! [`Pseudonymizer`] applies the [`Rules`] to each triple of the graph,
+//! looking up the types of nodes in the index.
+//!
+//! ```
+//! use std::path::Path;
+//! use tripsu::{new_pseudonymizer, parse_index, parse_rules, Pseudonymizer};
+//!
+//! # fn main() -> Result<(), tripsu::TripsuError> {
+//! let rules = parse_rules(Path::new("tests/data/rules.yaml"))?;
+//! let index = parse_index(Path::new("tests/data/type_index.json"))?;
+//! let pseudonymizer = Pseudonymizer::new(rules, index, new_pseudonymizer(None, None)?);
+//!
+//! let input = "<http://example.org/Alice> <http://xmlns.com/foaf/0.1/name> \"Alice\" .\n";
+//! let mut output = Vec::new();
+//! pseudonymizer.pseudonymize_ntriples(input.as_bytes(), &mut output)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Invalid input lines can be skipped or quarantined by an [`ErrorHandler`]
+//! instead of failing, and large inputs processed on several threads:
+//!
+//! ```
+//! use std::path::Path;
+//! use tripsu::{
+//!     build_type_index, new_pseudonymizer, parse_rules, ErrorHandler, OnError, Pseudonymizer,
+//! };
+//!
+//! # fn main() -> Result<(), tripsu::TripsuError> {
+//! let rules = parse_rules(Path::new("tests/data/rules.yaml"))?;
+//! let input = "<http://example.org/Alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> \
+//!              <http://example.org/Person> .\n\
+//!              not a triple\n";
+//!
+//! let mut errors = ErrorHandler::new(OnError::Skip, None)?;
+//! let index = build_type_index(input.as_bytes(), rules.propagations(), &mut errors)?;
+//! let pseudonymizer = Pseudonymizer::new(rules, index, new_pseudonymizer(None, None)?);
+//!
+//! let mut output = Vec::new();
+//! pseudonymizer.pseudonymize_ntriples_with(input.as_bytes(), &mut output, &mut errors)?;
+//! pseudonymizer.pseudonymize_ntriples_parallel(input.as_bytes(), &mut output, &mut errors, 2, true)?;
+//! assert_eq!(errors.invalid(), 3);
+//! # Ok(())
+//! # }
+//! ```
+
+pub(crate) mod check;
+pub(crate) mod coverage;
+pub(crate) mod crypto;
+pub(crate) mod detect;
+pub(crate) mod error;
+pub(crate) mod explain;
+pub(crate) mod index;
+pub(crate) mod io;
+pub(crate) mod log;
+pub(crate) mod model;
+pub(crate) mod pattern;
+pub(crate) mod pseudo;
+pub(crate) mod rdf_types;
+pub(crate) mod rules;
+pub(crate) mod schema;
+pub(crate) mod shacl;
+pub(crate) mod uris;
+
+pub use crate::{
+    crypto::{new_pseudonymizer, PseudoError, Pseudonymize},
+    error::TripsuError,
+    index::{build_type_index, build_type_index_parallel, TypeIndex},
+    io::{parse_index, parse_rules, ErrorHandler, OnError},
+    model::TripleMask,
+    pseudo::Pseudonymizer,
+    rdf_types::{Triple, TripleView},
+    rules::{Propagation, Rules},
+};
+
+/// Entry points of the command line interface, not part of the public API.
+#[doc(hidden)]
+pub mod cli {
+    pub use crate::{
+        check::check_rules_file,
+        coverage::coverage_graph,
+        explain::explain_graph,
+        index::create_type_index,
+        io::{convert_rules, Processing, RulesFormat},
+        log::create_logger,
+        pseudo::{pseudonymize_graph, run_graph},
+        schema::write_rules_schema,
+        shacl::{shacl_to_rules, SENSITIVE},
+    };
+}
@@ -1,23 +1,15 @@
-// Define the module.
-mod crypto;
-mod index;
-mod io;
-mod log;
-mod model;
-mod pseudo;
-mod rdf_types;
-mod rules;
-mod uris;
-
 // Define the imports.
-use crate::{
-    index::create_type_index,
-    log::{create_logger, info},
-    pseudo::pseudonymize_graph,
+use tripsu::{
+    cli::{
+        check_rules_file, convert_rules, coverage_graph, create_logger, create_type_index,
+        explain_graph, pseudonymize_graph, run_graph, shacl_to_rules, write_rules_schema,
+        Processing, RulesFormat, SENSITIVE,
+    },
+    log_error as error, log_info as info, ErrorHandler, OnError, TripsuError,
 };
 
-use clap::{Args, Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{error::ErrorKind, Args, CommandFactory, Parser, Subcommand};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "tripsu")]
@@ -38,6 +30,28 @@ struct IndexArgs {
     /// Defaults to `stdin`.
     #[arg(default_value = "-")]
     input: PathBuf,
+
+    /// File defining pseudonymization rules, used to
+    /// propagate types along property paths.
+    /// Format: yaml, json or toml, detected from the extension
+    #[arg(short, long)]
+    rules: Option<PathBuf>,
+
+    /// How to handle input lines which cannot be parsed:
+    /// stop, skip them, or write them to the quarantine file.
+    #[arg(long, value_enum, default_value_t = OnError::Fail)]
+    on_error: OnError,
+
+    /// File receiving the invalid input lines, each preceded by a comment
+    /// with its line number. Required with, and only accepted with,
+    /// `--on-error=quarantine`.
+    #[arg(long, required_if_eq("on_error", "quarantine"))]
+    quarantine: Option<PathBuf>,
+
+    /// Number of worker threads indexing chunks of the input.
+    /// 0 uses all available cores.
+    #[arg(short = 'j', long, default_value_t = 1)]
+    threads: usize,
 }
 
 #[derive(Args, Debug)]
@@ -53,7 +67,7 @@ struct PseudoArgs {
     input: PathBuf,
 
     /// File defining which RDF elements to pseudonymize.
-    /// Format: yaml
+    /// Format: yaml, json or toml, detected from the extension
     #[arg(short, long)]
     rules: PathBuf,
 
@@ -66,6 +80,181 @@ struct PseudoArgs {
     /// Default is to use a random key.
     #[arg(short, long, default_value=None)]
     secret: Option<PathBuf>,
+
+    /// Only evaluate the rules without pseudonymizing. A json report
+    /// with the number of matches of each rule, and the predicates and
+    /// types not covered by any rule, is written to the output instead.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// How to handle input lines which cannot be parsed:
+    /// stop, skip them, or write them to the quarantine file.
+    #[arg(long, value_enum, default_value_t = OnError::Fail)]
+    on_error: OnError,
+
+    /// File receiving the invalid input lines, each preceded by a comment
+    /// with its line number. Required with, and only accepted with,
+    /// `--on-error=quarantine`.
+    #[arg(long, required_if_eq("on_error", "quarantine"))]
+    quarantine: Option<PathBuf>,
+
+    /// Number of worker threads pseudonymizing chunks of the input.
+    /// 0 uses all available cores.
+    #[arg(short = 'j', long, default_value_t = 1)]
+    threads: usize,
+
+    /// With several threads, write the pseudonymized chunks as soon as
+    /// they are ready instead of in the order of the input.
+    #[arg(long)]
+    unordered: bool,
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// File descriptor to read input triples from.
+    /// Defaults to `stdin`, which is spilled to a temporary file.
+    #[arg(default_value = "-")]
+    input: PathBuf,
+
+    /// File defining which RDF elements to pseudonymize, and
+    /// the propagation of types along property paths.
+    /// Format: yaml, json or toml, detected from the extension
+    #[arg(short, long)]
+    rules: PathBuf,
+
+    /// Output file descriptor for pseudonymized triples.
+    /// Defaults to `stdout`.
+    #[arg(short, long, default_value = "-")]
+    output: PathBuf,
+
+    /// File containing the secret used to generate pseudonyms.
+    /// Default is to use a random key.
+    #[arg(short, long, default_value=None)]
+    secret: Option<PathBuf>,
+
+    /// How to handle input lines which cannot be parsed:
+    /// stop, skip them, or write them to the quarantine file.
+    #[arg(long, value_enum, default_value_t = OnError::Fail)]
+    on_error: OnError,
+
+    /// File receiving the invalid input lines, each preceded by a comment
+    /// with its line number. Required with, and only accepted with,
+    /// `--on-error=quarantine`.
+    #[arg(long, required_if_eq("on_error", "quarantine"))]
+    quarantine: Option<PathBuf>,
+
+    /// Number of worker threads indexing and pseudonymizing chunks
+    /// of the input. 0 uses all available cores.
+    #[arg(short = 'j', long, default_value_t = 1)]
+    threads: usize,
+
+    /// With several threads, write the pseudonymized chunks as soon as
+    /// they are ready instead of in the order of the input.
+    #[arg(long)]
+    unordered: bool,
+}
+
+#[derive(Args, Debug)]
+struct ExplainArgs {
+    /// Index file produced by prepare-index.
+    #[arg(short = 'x', long)]
+    index: PathBuf,
+
+    /// File descriptor to read input triples from.
+    /// Defaults to `stdin`.
+    #[arg(default_value = "-")]
+    input: PathBuf,
+
+    /// File defining which RDF elements to pseudonymize.
+    /// Format: yaml, json or toml, detected from the extension
+    #[arg(short, long)]
+    rules: PathBuf,
+
+    /// Output file descriptor for the explanations, one json object per triple.
+    /// Defaults to `stdout`.
+    #[arg(short, long, default_value = "-")]
+    output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct RulesArgs {
+    #[command(subcommand)]
+    command: RulesSubcommands,
+}
+
+#[derive(Args, Debug)]
+struct CheckArgs {
+    /// File defining pseudonymization rules.
+    /// Format: yaml, json or toml, detected from the extension
+    rules: PathBuf,
+
+    /// Index file produced by prepare-index, used to warn
+    /// about types which never appear in the data.
+    #[arg(short = 'x', long)]
+    index: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct SchemaArgs {
+    /// Output file descriptor for the JSON Schema.
+    /// Defaults to `stdout`.
+    #[arg(short, long, default_value = "-")]
+    output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct FromShaclArgs {
+    /// File descriptor to read SHACL shapes from.
+    /// Format: turtle
+    /// Defaults to `stdin`.
+    #[arg(default_value = "-")]
+    input: PathBuf,
+
+    /// Output file descriptor for the rules.
+    /// Defaults to `stdout`.
+    #[arg(short, long, default_value = "-")]
+    output: PathBuf,
+
+    /// Predicate annotating sensitive node and property shapes
+    /// with a boolean value.
+    #[arg(short, long, default_value = SENSITIVE)]
+    annotation: String,
+}
+
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    /// Rules file to convert.
+    /// Format: yaml, json or toml, detected from the extension
+    input: PathBuf,
+
+    /// Output file descriptor for the converted rules.
+    /// Defaults to `stdout`.
+    #[arg(short, long, default_value = "-")]
+    output: PathBuf,
+
+    /// Format of the converted rules.
+    /// Defaults to the format of the output extension.
+    #[arg(short, long)]
+    to: Option<RulesFormat>,
+}
+
+#[derive(Subcommand, Debug)]
+enum RulesSubcommands {
+    /// Validate a rules file and warn about common mistakes.
+    // Problems are reported with their line and column in the rules file.
+    Check(CheckArgs),
+
+    /// Print the JSON Schema of the rules file format.
+    // Editors can use the schema to validate and complete rules files.
+    Schema(SchemaArgs),
+
+    /// Create rules from SHACL shapes annotated as sensitive.
+    // Annotated property shapes give `on_type_predicate` entries for the target
+    // classes of their node shape, and annotated node shapes give `of_type` entries.
+    FromShacl(FromShaclArgs),
+
+    /// Convert a rules file between yaml, json and toml.
+    Convert(ConvertArgs),
 }
 
 #[derive(Subcommand, Debug)]
@@ -79,28 +268,145 @@ enum Subcommands {
     // A config file defines pseudonymization rules. The deidentified triples are sent to the
     // output file descriptor. (default `stdout`)
     Pseudo(PseudoArgs),
+
+    /// Index and pseudonymize input triples in a single run.
+    // The index is built in memory from a first pass over the input, without
+    // an index file. Input from `stdin` is spilled to a temporary file.
+    Run(RunArgs),
+
+    /// Explain which rules match each input triple.
+    // Each triple is written as a json line with the matched rules, the index
+    // types causing the matches, and whether subject and object are pseudonymized.
+    Explain(ExplainArgs),
+
+    /// Work with rules files.
+    Rules(RulesArgs),
+}
+
+/// Create the handler of invalid input lines, exiting with a usage error
+/// when a quarantine file is given without `--on-error=quarantine`.
+fn error_handler(
+    command: &str,
+    on_error: OnError,
+    quarantine: Option<&Path>,
+) -> Result<ErrorHandler, TripsuError> {
+    if quarantine.is_some() && on_error != OnError::Quarantine {
+        let mut cli = Cli::command();
+        cli.build();
+        cli.find_subcommand_mut(command)
+            .expect("Unknown subcommand")
+            .error(
+                ErrorKind::ArgumentConflict,
+                "the argument '--quarantine <QUARANTINE>' requires '--on-error=quarantine'",
+            )
+            .exit();
+    }
+    ErrorHandler::new(on_error, quarantine)
 }
 
 fn main() {
+    // Panics are unexpected failures, which exit with code 1.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        std::process::exit(1);
+    }));
+
     let log = create_logger(false);
     let cli = Cli::parse();
 
-    match cli.command {
+    let r = match cli.command {
         Subcommands::Index(args) => {
             info!(log, "Args: {:?}", args);
-            create_type_index(&args.input, &args.output)
+            error_handler("index", args.on_error, args.quarantine.as_deref()).and_then(|errors| {
+                let mut processing = Processing {
+                    errors,
+                    threads: args.threads,
+                    ..Default::default()
+                };
+                create_type_index(
+                    &log,
+                    &args.input,
+                    &args.output,
+                    &args.rules,
+                    &mut processing,
+                )
+            })
         }
         Subcommands::Pseudo(args) => {
             info!(log, "Args: {:?}", args);
-            pseudonymize_graph(
-                &log,
-                &args.input,
-                &args.rules,
-                &args.output,
-                &args.index,
-                &args.secret,
+            error_handler("pseudo", args.on_error, args.quarantine.as_deref()).and_then(
+                |mut errors| {
+                    if args.dry_run {
+                        coverage_graph(
+                            &log,
+                            &args.input,
+                            &args.rules,
+                            &args.output,
+                            &args.index,
+                            &mut errors,
+                        )
+                    } else {
+                        pseudonymize_graph(
+                            &log,
+                            &args.input,
+                            &args.rules,
+                            &args.output,
+                            &args.index,
+                            &args.secret,
+                            &mut Processing {
+                                errors,
+                                threads: args.threads,
+                                ordered: !args.unordered,
+                            },
+                        )
+                    }
+                },
             )
         }
+        Subcommands::Run(args) => {
+            info!(log, "Args: {:?}", args);
+            error_handler("run", args.on_error, args.quarantine.as_deref()).and_then(|errors| {
+                run_graph(
+                    &log,
+                    &args.input,
+                    &args.rules,
+                    &args.output,
+                    &args.secret,
+                    &mut Processing {
+                        errors,
+                        threads: args.threads,
+                        ordered: !args.unordered,
+                    },
+                )
+            })
+        }
+        Subcommands::Explain(args) => {
+            info!(log, "Args: {:?}", args);
+            explain_graph(&log, &args.input, &args.rules, &args.output, &args.index)
+        }
+        Subcommands::Rules(args) => match args.command {
+            RulesSubcommands::Check(args) => {
+                info!(log, "Args: {:?}", args);
+                check_rules_file(&log, &args.rules, &args.index)
+            }
+            RulesSubcommands::Schema(args) => write_rules_schema(&args.output),
+            RulesSubcommands::FromShacl(args) => {
+                info!(log, "Args: {:?}", args);
+                shacl_to_rules(&log, &args.input, &args.output, &args.annotation)
+            }
+            RulesSubcommands::Convert(args) => {
+                info!(log, "Args: {:?}", args);
+                convert_rules(&args.input, &args.output, args.to)
+            }
+        },
+    };
+
+    // Each error category exits with its own code, see `TripsuError`.
+    if let Err(e) = r {
+        error!(log, "{e}");
+        drop(log);
+        std::process::exit(e.exit_code());
     }
 }
 
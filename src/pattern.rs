@@ -0,0 +1,179 @@
+use crate::{
+    rules::strip_brackets,
+    uris::{PrefixError, PrefixMap, Uri},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::Range};
+
+/// Raw representation of a pattern in the rules file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+struct PatternDef {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regex: Option<String>,
+}
+
+/// A pattern matching IRIs or literal values.
+/// Regular expressions are compiled once when the rules are loaded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "PatternDef", into = "PatternDef")]
+pub enum Pattern {
+    /// Match values starting with the given string.
+    Prefix(String),
+    /// Match values containing a match of the regular expression.
+    Regex(Regex),
+}
+
+impl TryFrom<PatternDef> for Pattern {
+    type Error = String;
+    fn try_from(def: PatternDef) -> Result<Self, Self::Error> {
+        match (def.prefix, def.regex) {
+            (Some(p), None) => Ok(Pattern::Prefix(p)),
+            (None, Some(r)) => Regex::new(&r)
+                .map(Pattern::Regex)
+                .map_err(|e| format!("Invalid regex pattern '{r}': {e}")),
+            _ => Err("A pattern must define exactly one of 'prefix' or 'regex'.".to_string()),
+        }
+    }
+}
+
+impl From<Pattern> for PatternDef {
+    fn from(pattern: Pattern) -> Self {
+        match pattern {
+            Pattern::Prefix(p) => PatternDef {
+                prefix: Some(p),
+                ..Default::default()
+            },
+            Pattern::Regex(r) => PatternDef {
+                regex: Some(r.as_str().to_string()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Prefix(p) => write!(f, "prefix: {p}"),
+            Pattern::Regex(r) => write!(f, "regex: {r}"),
+        }
+    }
+}
+
+impl Pattern {
+    /// Check if the value matches the pattern.
+    pub fn is_match(&self, value: &str) -> bool {
+        match self {
+            Pattern::Prefix(p) => value.starts_with(p.as_str()),
+            Pattern::Regex(r) => r.is_match(value),
+        }
+    }
+
+    /// Find the byte ranges of non-empty regex matches in the value.
+    /// Prefix patterns do not define spans.
+    pub fn find_spans(&self, value: &str) -> Vec<Range<usize>> {
+        match self {
+            Pattern::Prefix(_) => Vec::new(),
+            Pattern::Regex(r) => r
+                .find_iter(value)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.range())
+                .collect(),
+        }
+    }
+
+    /// Expand the namespace of an IRI prefix pattern with the given prefixes.
+    /// The prefix can be a full URI in brackets, a cURIe, or a bare prefix
+    /// name such as `ex:` to match the whole namespace.
+    pub fn expand_iri(&self, prefixes: &PrefixMap) -> Result<Pattern, PrefixError> {
+        match self {
+            Pattern::Prefix(p) if p.starts_with('<') => {
+                let uri = Uri::try_from(p.clone())?;
+                Ok(Pattern::Prefix(
+                    strip_brackets(&uri.to_string()).to_string(),
+                ))
+            }
+            Pattern::Prefix(p) => match prefixes.expand_curie(&Uri::CompactUri(p.clone()))? {
+                Uri::FullUri(iri) => Ok(Pattern::Prefix(iri)),
+                Uri::CompactUri(curie) => Err(PrefixError::InvalidPrefix(curie)),
+            },
+            Pattern::Regex(_) => Ok(self.clone()),
+        }
+    }
+}
+
+/// (De)serialize an optional regex from its string form,
+/// compiling it once when the rules are loaded.
+pub mod serde_regex {
+    use regex::Regex;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(re: &Option<Regex>, serializer: S) -> Result<S::Ok, S::Error> {
+        re.as_ref().map(Regex::as_str).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Regex>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(r) => Regex::new(&r)
+                .map(Some)
+                .map_err(|e| D::Error::custom(format!("Invalid regex pattern '{r}': {e}"))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Expand all IRI prefix patterns with the given prefixes.
+pub fn expand_iri_patterns(
+    patterns: &[Pattern],
+    prefixes: &PrefixMap,
+) -> Result<Vec<Pattern>, PrefixError> {
+    patterns.iter().map(|p| p.expand_iri(prefixes)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::collections::HashMap;
+
+    #[rstest]
+    // Prefix given as full URI
+    #[case(
+        "prefix: <http://example.org/private/>",
+        "http://example.org/private/x",
+        true
+    )]
+    // Prefix given as bare namespace
+    #[case("prefix: 'ex:'", "http://example.org/x", true)]
+    // Prefix given as cURIe
+    #[case("prefix: ex:private/", "http://example.org/public/x", false)]
+    // Unanchored regex
+    #[case(r"regex: '756\.\d{4}\.\d{4}\.\d{2}'", "AHV: 756.1234.5678.97", true)]
+    // Anchored regex
+    #[case(r"regex: '^756'", "AHV: 756.1234.5678.97", false)]
+    fn pattern_match(#[case] yml: &str, #[case] value: &str, #[case] match_expected: bool) {
+        let prefixes = PrefixMap::from_hashmap(&HashMap::from([(
+            Some("ex".to_string()),
+            "<http://example.org/>".to_string(),
+        )]))
+        .unwrap();
+        let pattern: Pattern = serde_yml::from_str(yml).unwrap();
+        let pattern = pattern.expand_iri(&prefixes).unwrap();
+
+        assert_eq!(pattern.is_match(value), match_expected);
+    }
+
+    #[test]
+    fn invalid_regex() {
+        let err = serde_yml::from_str::<Pattern>("regex: '(unclosed'").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid regex pattern '(unclosed'"));
+    }
+}
@@ -1,87 +1,277 @@
-use rio_api::parser::TriplesParser;
-use rio_turtle::TurtleError;
 use std::{
-    io::Write,
+    io::{stdin, BufRead, Read, Write},
     path::{Path, PathBuf},
 };
 
 use crate::{
-    crypto::{new_pseudonymizer, Pseudonymize},
-    index::TypeIndex,
-    io,
-    log::Logger,
+    crypto::{new_pseudonymizer, PseudoError, Pseudonymize, PseudonymizeTerms},
+    error::TripsuError,
+    index::{build_type_index, build_type_index_parallel, TypeIndex},
+    io::{self, ErrorHandler, Processing},
+    log::{info, Logger},
     model::TripleMask,
     rdf_types::*,
-    rules::{match_rules, Rules},
+    rules::{match_literal_spans, match_rules, Rules},
 };
 
-// mask and encode input triple
-// NOTE: This will need the type-map to perform masking
-fn process_triple(
-    triple: TripleView,
-    rules_config: &Rules,
-    node_to_type: &mut TypeIndex,
-    out: &mut impl Write,
-    hasher: &dyn Pseudonymize,
-) {
-    let mask = match_rules(&triple, rules_config, node_to_type);
-
-    let r = || -> std::io::Result<()> {
-        // If nothing needs to be pseudonymized, directly return triple
-        if !mask.is_set(&TripleMask::SUBJECT) & !mask.is_set(&TripleMask::OBJECT) {
-            out.write_all(triple.to_string().as_bytes())?;
-            out.write_all(b" .\n")?;
-        } else {
-            let pseudo_triple = hasher.pseudo_triple(&triple.into(), mask);
-            out.write_all(pseudo_triple.to_string().as_bytes())?;
-            out.write_all(b" .\n")?;
+/// Streaming pseudonymization of the triples of a graph, following
+/// the rules and the type index of the graph.
+pub struct Pseudonymizer<P: Pseudonymize> {
+    rules: Rules,
+    index: TypeIndex,
+    hasher: P,
+}
+
+impl<P: Pseudonymize> Pseudonymizer<P> {
+    pub fn new(rules: Rules, index: TypeIndex, hasher: P) -> Self {
+        Pseudonymizer {
+            rules,
+            index,
+            hasher,
+        }
+    }
+
+    // mask and encode input triple, returns None if the triple is unchanged.
+    fn process(&self, triple: &TripleView) -> Result<Option<Triple>, PseudoError> {
+        let mask = match_rules(triple, &self.rules, &self.index);
+
+        // Spans of literals which are not pseudonymized as a whole
+        let spans = match mask.is_set(&TripleMask::OBJECT) {
+            false => match_literal_spans(triple, &self.rules, &self.index),
+            true => Vec::new(),
+        };
+
+        // If nothing needs to be pseudonymized or rewritten, directly return triple
+        if !mask.is_set(&TripleMask::SUBJECT)
+            & !mask.is_set(&TripleMask::OBJECT)
+            & !self.rules.iris.rewrite_all
+            & spans.is_empty()
+        {
+            return Ok(None);
         }
+
+        let mut pseudo_triple =
+            self.hasher
+                .pseudo_triple(&(*triple).into(), mask, &self.rules.iris)?;
+        if let Term::Literal(l) = &pseudo_triple.object {
+            if !spans.is_empty() {
+                pseudo_triple.object = Term::Literal(self.hasher.pseudo_literal_spans(l, &spans));
+            }
+        }
+        Ok(Some(pseudo_triple))
+    }
+
+    /// Pseudonymize a parsed triple.
+    pub fn pseudonymize(&self, triple: &TripleView) -> Result<Triple, PseudoError> {
+        Ok(self
+            .process(triple)?
+            .unwrap_or_else(|| Triple::from(*triple)))
+    }
+
+    /// Pseudonymize a triple.
+    pub fn pseudonymize_triple(&self, triple: &Triple) -> Result<Triple, PseudoError> {
+        Ok(self
+            .process(&triple.as_view())?
+            .unwrap_or_else(|| triple.clone()))
+    }
+
+    /// Pseudonymize a stream of triples, yielding the output triples in order.
+    pub fn pseudonymize_iter<'a>(
+        &'a self,
+        triples: impl IntoIterator<Item = Triple> + 'a,
+    ) -> impl Iterator<Item = Result<Triple, PseudoError>> + 'a {
+        triples
+            .into_iter()
+            .map(move |triple| self.pseudonymize_triple(&triple))
+    }
+
+    /// Pseudonymize N-Triples from the input, written as N-Triples to the output.
+    pub fn pseudonymize_ntriples(
+        &self,
+        input: impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<(), TripsuError> {
+        self.pseudonymize_ntriples_with(input, output, &mut ErrorHandler::default())
+    }
+
+    /// Pseudonymize N-Triples from the input, with invalid lines
+    /// handled by the error handler.
+    pub fn pseudonymize_ntriples_with(
+        &self,
+        input: impl BufRead,
+        output: &mut impl Write,
+        errors: &mut ErrorHandler,
+    ) -> Result<(), TripsuError> {
+        // Run the loop single-threaded.
+        io::parse_ntriples_with(input, errors, &mut |t: TripleView| {
+            self.write_triple(&t, output)
+        })
+    }
+
+    fn write_triple(&self, triple: &TripleView, out: &mut impl Write) -> Result<(), TripsuError> {
+        let line = match self.process(triple)? {
+            Some(pseudo_triple) => pseudo_triple.to_string(),
+            None => triple.to_string(),
+        };
+        out.write_all(line.as_bytes())?;
+        out.write_all(b" .\n")?;
         Ok(())
-    }();
+    }
+}
 
-    if let Err(e) = r {
-        panic!("Error writting to out buffer: {e}");
+impl<P: Pseudonymize + Sync> Pseudonymizer<P> {
+    /// Pseudonymize N-Triples from the input on worker threads, each
+    /// processing chunks of whole lines. The output keeps the order of the
+    /// input if `ordered`, otherwise chunks are written as soon as processed.
+    pub fn pseudonymize_ntriples_parallel(
+        &self,
+        input: impl BufRead + Send,
+        output: &mut impl Write,
+        errors: &mut ErrorHandler,
+        threads: usize,
+        ordered: bool,
+    ) -> Result<(), TripsuError> {
+        // Workers collect the invalid lines of their chunk, merged with the output
+        let chunk_errors = errors.buffered();
+        io::process_chunks(
+            input,
+            io::CHUNK_SIZE,
+            threads,
+            ordered,
+            |chunk, lines_before| {
+                let mut errors = chunk_errors.buffered();
+                let mut out = Vec::with_capacity(chunk.len());
+                io::parse_ntriples_lines(chunk, lines_before, &mut errors, &mut |t| {
+                    self.write_triple(&t, &mut out)
+                })
+                .map(|_| (out, errors))
+            },
+            |result| {
+                let (out, chunk_errors) = result?;
+                output.write_all(&out)?;
+                errors.merge(chunk_errors)
+            },
+        )
     }
 }
 
 pub fn pseudonymize_graph(
-    _: &Logger,
+    log: &Logger,
     input: &Path,
     rules_path: &Path,
     output: &Path,
     index_path: &Path,
     secret_path: &Option<PathBuf>,
-) {
-    let buf_input = io::get_reader(input);
-    let mut buf_output = io::get_writer(output);
+    processing: &mut Processing,
+) -> Result<(), TripsuError> {
+    let rules = io::parse_rules(rules_path)?;
+    let type_index = io::parse_index(index_path)?;
 
-    let rules = io::parse_rules(rules_path);
-    let mut type_index = io::parse_index(index_path);
+    let secret = secret_path.as_ref().map(io::read_bytes).transpose()?;
+    let pseudonymizer = Pseudonymizer::new(rules, type_index, new_pseudonymizer(None, secret)?);
 
-    let secret = secret_path.as_ref().map(io::read_bytes);
-    let pseudonymizer = new_pseudonymizer(None, secret);
+    pseudonymize_file(log, &pseudonymizer, input, output, processing)
+}
 
-    let mut triples = io::parse_ntriples(buf_input);
+/// Index the input in memory, then pseudonymize it without an index file.
+/// The input is read twice, input from stdin is first spilled to a
+/// temporary file.
+pub fn run_graph(
+    log: &Logger,
+    input: &Path,
+    rules_path: &Path,
+    output: &Path,
+    secret_path: &Option<PathBuf>,
+    processing: &mut Processing,
+) -> Result<(), TripsuError> {
+    run_graph_from(
+        log,
+        input,
+        stdin(),
+        rules_path,
+        output,
+        secret_path,
+        processing,
+    )
+}
 
-    // Run the loop single-threaded.
-    while !triples.is_end() {
-        triples
-            .parse_step(&mut |t: TripleView| {
-                process_triple(t, &rules, &mut type_index, &mut buf_output, &pseudonymizer);
-                Result::<(), TurtleError>::Ok(())
-            })
-            .inspect_err(|e| {
-                panic!("Parsing error occured: {e}");
-            })
-            .unwrap();
+/// Single run reading `stdin` when the input is `-`.
+fn run_graph_from(
+    log: &Logger,
+    input: &Path,
+    stdin: impl Read,
+    rules_path: &Path,
+    output: &Path,
+    secret_path: &Option<PathBuf>,
+    processing: &mut Processing,
+) -> Result<(), TripsuError> {
+    let rules = io::parse_rules(rules_path)?;
+    let secret = secret_path.as_ref().map(io::read_bytes).transpose()?;
+    let hasher = new_pseudonymizer(None, secret)?;
+
+    let spilled = match input.to_str() {
+        Some("-") => Some(io::spill_stdin(stdin)?),
+        _ => None,
+    };
+    let input = spilled.as_ref().map_or(input, |file| file.path());
+
+    // Invalid lines are only reported by the pseudonymization pass
+    let threads = processing.threads();
+    let mut errors = processing.errors.skipping();
+    let buf_input = io::get_reader(input)?;
+    let propagations = rules.propagations();
+    let type_index = match threads {
+        1 => build_type_index(buf_input, propagations, &mut errors)?,
+        threads => build_type_index_parallel(buf_input, propagations, &mut errors, threads)?,
+    };
+    info!(log, "Indexed {} types", type_index.types.len());
+
+    let pseudonymizer = Pseudonymizer::new(rules, type_index, hasher);
+    pseudonymize_file(log, &pseudonymizer, input, output, processing)
+}
+
+fn pseudonymize_file<P: Pseudonymize + Sync>(
+    log: &Logger,
+    pseudonymizer: &Pseudonymizer<P>,
+    input: &Path,
+    output: &Path,
+    processing: &mut Processing,
+) -> Result<(), TripsuError> {
+    let buf_input = io::get_reader(input)?;
+    let mut buf_output = io::get_writer(output)?;
+    let (threads, ordered) = (processing.threads(), processing.ordered);
+    let errors = &mut processing.errors;
+    match threads {
+        1 => pseudonymizer.pseudonymize_ntriples_with(buf_input, &mut buf_output, errors)?,
+        threads => pseudonymizer.pseudonymize_ntriples_parallel(
+            buf_input,
+            &mut buf_output,
+            errors,
+            threads,
+            ordered,
+        )?,
     }
+    buf_output.flush()?;
+    errors.finish(log)?;
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::pseudonymize_graph;
-    use crate::log;
-    use std::path::Path;
+    use super::{pseudonymize_graph, run_graph, run_graph_from, Pseudonymizer};
+    use crate::{
+        crypto::new_pseudonymizer,
+        index::TypeIndex,
+        io::{self, ErrorHandler, OnError, Processing},
+        log,
+        rdf_types::*,
+        rules::Rules,
+    };
+    use rio_api::parser::TriplesParser;
+    use rio_turtle::TurtleError;
+    use rstest::rstest;
+    use std::{fs, fs::File, path::Path};
     use tempfile::tempdir;
 
     #[test]
@@ -102,6 +292,203 @@ mod tests {
             &output_path,
             type_map_path,
             &key,
+            &mut Processing::default(),
+        )
+        .unwrap();
+    }
+
+    #[rstest]
+    #[case("missing.nt", "rules.yaml", "type_index.json", None, 3)]
+    #[case("bad.nt", "rules.yaml", "type_index.json", None, 4)]
+    #[case("test.nt", "missing.yaml", "type_index.json", None, 5)]
+    #[case("test.nt", "rules.yaml", "rules.yaml", None, 6)]
+    #[case("test.nt", "rules.yaml", "type_index.json", Some("short.key"), 7)]
+    // Failures are reported by category instead of panicking.
+    fn pseudo_errors(
+        #[case] input: &str,
+        #[case] rules: &str,
+        #[case] index: &str,
+        #[case] key: Option<&str>,
+        #[case] exit_code: i32,
+    ) {
+        let logger = log::create_logger(true);
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("bad.nt"), "<urn:Alice> <urn:knows>\n").unwrap();
+        fs::write(dir.path().join("short.key"), "secret").unwrap();
+        let data = |name: &str| match name {
+            "bad.nt" | "short.key" => dir.path().join(name),
+            _ => Path::new("tests/data").join(name),
+        };
+
+        let err = pseudonymize_graph(
+            &logger,
+            &data(input),
+            &data(rules),
+            &dir.path().join("output.nt"),
+            &data(index),
+            &key.map(data),
+            &mut Processing::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.exit_code(), exit_code, "{err}");
+    }
+
+    #[rstest]
+    #[case(true)]
+    #[case(false)]
+    // Worker threads give the same output as a single thread, and report
+    // invalid lines with their line number in the whole input.
+    fn pseudo_parallel(#[case] ordered: bool) {
+        let logger = log::create_logger(true);
+        let dir = tempdir().unwrap();
+        let mut input = fs::read_to_string("tests/data/test.nt").unwrap();
+        input.push_str("not a triple\n");
+        let lines = input.lines().count();
+        let input_path = dir.path().join("input.nt");
+        fs::write(&input_path, &input).unwrap();
+        fs::write(dir.path().join("secret.key"), [7; 32]).unwrap();
+
+        let run = |threads: usize, name: &str| {
+            let output = dir.path().join(format!("{name}.nt"));
+            let quarantine = dir.path().join(format!("{name}.quarantine.nt"));
+            pseudonymize_graph(
+                &logger,
+                &input_path,
+                Path::new("tests/data/rules.yaml"),
+                &output,
+                Path::new("tests/data/type_index.json"),
+                &Some(dir.path().join("secret.key")),
+                &mut Processing {
+                    errors: ErrorHandler::new(OnError::Quarantine, Some(&quarantine)).unwrap(),
+                    threads,
+                    ordered,
+                },
+            )
+            .unwrap();
+            let mut output: Vec<_> = fs::read_to_string(output)
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect();
+            if !ordered {
+                output.sort();
+            }
+            (output, fs::read_to_string(quarantine).unwrap())
+        };
+
+        let (sequential, sequential_quarantine) = run(1, "sequential");
+        let (parallel, parallel_quarantine) = run(4, "parallel");
+        assert_eq!(sequential.len(), lines - 1);
+        assert_eq!(parallel, sequential);
+        assert!(parallel_quarantine.starts_with(&format!("# line {lines}: ")));
+        assert_eq!(parallel_quarantine, sequential_quarantine);
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(4)]
+    // A single run gives the same output as indexing and pseudonymizing.
+    fn run_nt_file(#[case] threads: usize) {
+        let logger = log::create_logger(true);
+        let dir = tempdir().unwrap();
+        let input_path = Path::new("tests/data/test.nt");
+        let rules_path = Path::new("tests/data/rules.yaml");
+        let index_path = dir.path().join("index.json");
+        let key = Some(dir.path().join("secret.key"));
+        fs::write(key.as_ref().unwrap(), [7; 32]).unwrap();
+        let processing = || Processing {
+            threads,
+            ..Default::default()
+        };
+
+        crate::index::create_type_index(
+            &logger,
+            input_path,
+            &index_path,
+            &Some(rules_path.to_path_buf()),
+            &mut processing(),
+        )
+        .unwrap();
+        let two_pass = dir.path().join("two_pass.nt");
+        pseudonymize_graph(
+            &logger,
+            input_path,
+            rules_path,
+            &two_pass,
+            &index_path,
+            &key,
+            &mut processing(),
+        )
+        .unwrap();
+        let single_pass = dir.path().join("single_pass.nt");
+        run_graph(
+            &logger,
+            input_path,
+            rules_path,
+            &single_pass,
+            &key,
+            &mut processing(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(single_pass).unwrap(),
+            fs::read_to_string(&two_pass).unwrap()
+        );
+
+        // Input from stdin is spilled to a temporary file read by both passes
+        let spilled = dir.path().join("spilled.nt");
+        run_graph_from(
+            &logger,
+            Path::new("-"),
+            File::open(input_path).unwrap(),
+            rules_path,
+            &spilled,
+            &key,
+            &mut processing(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(spilled).unwrap(),
+            fs::read_to_string(two_pass).unwrap()
         );
     }
+
+    #[test]
+    // Owned triples, parsed triples and N-Triples give the same output.
+    fn pseudonymizer_stream() {
+        let rules: Rules = serde_yml::from_str("nodes:\n  of_type: [\"<urn:Person>\"]\n").unwrap();
+        let index = TypeIndex::from_iter(vec![("<urn:Alice>", "<urn:Person>")]);
+        let pseudonymizer = Pseudonymizer::new(
+            rules.expand_rules_curie().unwrap(),
+            index,
+            new_pseudonymizer(None, Some([7; 32].to_vec())).unwrap(),
+        );
+        let input = "<urn:Alice> <urn:knows> <urn:Bob> .\n<urn:Bob> <urn:name> \"Bob\"@en .\n";
+
+        let mut triples = Vec::new();
+        io::parse_ntriples(input.as_bytes())
+            .parse_all(&mut |t: TripleView| {
+                triples.push((Triple::from(t), pseudonymizer.pseudonymize(&t).unwrap()));
+                Result::<(), TurtleError>::Ok(())
+            })
+            .unwrap();
+        let (triples, parsed): (Vec<_>, Vec<_>) = triples.into_iter().unzip();
+
+        let owned: Vec<Triple> = pseudonymizer
+            .pseudonymize_iter(triples.clone())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let mut output = Vec::new();
+        pseudonymizer
+            .pseudonymize_ntriples(input.as_bytes(), &mut output)
+            .unwrap();
+
+        assert_eq!(owned, parsed);
+        assert_ne!(owned[0].subject, triples[0].subject);
+        assert_eq!(owned[1], triples[1]);
+        let lines: Vec<_> = owned.iter().map(|t| format!("{t} .\n")).collect();
+        assert_eq!(String::from_utf8(output).unwrap(), lines.concat());
+    }
 }
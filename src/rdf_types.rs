@@ -127,6 +127,42 @@ impl fmt::Display for BlankNode {
     }
 }
 
+impl Triple {
+    /// Borrow the triple as a rio triple.
+    pub fn as_view(&self) -> TripleView<'_> {
+        TripleView {
+            subject: match &self.subject {
+                Subject::NamedNode(node) => SubjectView::NamedNode(node.as_view()),
+                Subject::BlankNode(node) => SubjectView::BlankNode(BlankNodeView { id: &node.id }),
+            },
+            predicate: self.predicate.as_view(),
+            object: match &self.object {
+                Term::NamedNode(node) => TermView::NamedNode(node.as_view()),
+                Term::BlankNode(node) => TermView::BlankNode(BlankNodeView { id: &node.id }),
+                Term::Literal(Literal::Simple { value }) => {
+                    TermView::Literal(LiteralView::Simple { value })
+                }
+                Term::Literal(Literal::LanguageTaggedString { value, language }) => {
+                    TermView::Literal(LiteralView::LanguageTaggedString { value, language })
+                }
+                Term::Literal(Literal::Typed { value, datatype }) => {
+                    TermView::Literal(LiteralView::Typed {
+                        value,
+                        datatype: datatype.as_view(),
+                    })
+                }
+            },
+        }
+    }
+}
+
+impl NamedNode {
+    /// Borrow the node as a rio named node.
+    pub fn as_view(&self) -> NamedNodeView<'_> {
+        NamedNodeView { iri: &self.iri }
+    }
+}
+
 impl<'a> From<TripleView<'a>> for Triple {
     fn from(t: TripleView<'a>) -> Self {
         Triple {
@@ -1,15 +1,97 @@
-use crate::{index::TypeIndex, model::TripleMask, uris::*};
-use ::std::collections::{HashMap, HashSet};
-use anyhow::{Error, Result};
+use crate::{
+    detect::Detector,
+    index::TypeIndex,
+    model::TripleMask,
+    pattern::{expand_iri_patterns, serde_regex, Pattern},
+    uris::*,
+};
+use ::std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ops::Range,
+    path::PathBuf,
+};
+use anyhow::{anyhow, Error, Result};
+use regex::Regex;
 use rio_api::model::*;
 use serde::{Deserialize, Serialize};
 
+const XSD_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+const RDF_LANG_STRING: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString";
+
 /// Rules for pseudonymizing nodes
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct NodeRules {
+    // Replace all nodes except the matched ones.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub invert: bool,
     // Replace values of nodes with a certain type.
-    #[serde(default)]
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "sorted_set"
+    )]
     of_type: HashSet<String>,
+    // Replace values of nodes whose IRI matches a pattern.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    matching: Vec<Pattern>,
+    // Replace values of subjects whose object has a certain type.
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "sorted_set"
+    )]
+    with_object_type: HashSet<String>,
+    // Propagate types along property paths when indexing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub propagate: Vec<Propagation>,
+}
+
+/// Propagation of sensitivity along a property path: nodes reached from
+/// instances of `from_type` by following the predicates of `path` in sequence
+/// are given the type `as_type` in the index.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Propagation {
+    pub from_type: String,
+    #[serde(deserialize_with = "non_empty_path")]
+    pub path: Vec<String>,
+    pub as_type: String,
+}
+
+/// Deserialize a propagation path, which must follow at least one predicate.
+fn non_empty_path<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    let path = Vec::<String>::deserialize(deserializer)?;
+    if path.is_empty() {
+        return Err(serde::de::Error::custom(
+            "a propagation path must list at least one predicate",
+        ));
+    }
+    Ok(path)
+}
+
+impl Propagation {
+    /// URIs and CURIEs used in the propagation.
+    fn uris(&self) -> impl Iterator<Item = &String> {
+        [&self.from_type, &self.as_type]
+            .into_iter()
+            .chain(self.path.iter())
+    }
+
+    /// Expand the cURIs of the propagation given the prefixes provided
+    pub fn expand_curies(&self, prefixes: &PrefixMap) -> Result<Propagation, PrefixError> {
+        let expand = |uri: &String| -> Result<String, PrefixError> {
+            Ok(Uri::try_from(uri.clone())?.expand(prefixes)?.to_string())
+        };
+
+        Ok(Propagation {
+            from_type: expand(&self.from_type)?,
+            path: self.path.iter().map(expand).collect::<Result<_, _>>()?,
+            as_type: expand(&self.as_type)?,
+        })
+    }
 }
 
 impl NodeRules {
@@ -18,6 +100,11 @@ impl NodeRules {
     pub fn check_uris(&self, prefixes: &PrefixMap) -> Result<(), anyhow::Error> {
         let uris = UriSet::try_from(self.of_type.clone())?;
         uris.expand(prefixes)?;
+        expand_iri_patterns(&self.matching, prefixes)?;
+        UriSet::try_from(self.with_object_type.clone())?.expand(prefixes)?;
+        for p in self.propagate.iter() {
+            p.expand_curies(prefixes)?;
+        }
         Ok(())
     }
 
@@ -27,21 +114,183 @@ impl NodeRules {
             .unwrap()
             .expand(prefixes)?;
 
+        let with_object_type = UriSet::try_from(self.with_object_type.clone())
+            .unwrap()
+            .expand(prefixes)?;
+
         Ok(NodeRules {
+            invert: self.invert,
             of_type: expanded.into(),
+            matching: expand_iri_patterns(&self.matching, prefixes)?,
+            with_object_type: with_object_type.into(),
+            propagate: self
+                .propagate
+                .iter()
+                .map(|p| p.expand_curies(prefixes))
+                .collect::<Result<_, _>>()?,
         })
     }
 }
 
 /// Rules for pseudonymizing objects
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ObjectRules {
+    /// Replace all objects except the matched ones.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub invert: bool,
     /// Replace values in matched `predicates`.
-    #[serde(default)]
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "sorted_set"
+    )]
     on_predicate: HashSet<String>,
     /// Replace values of predicates for specific types
-    #[serde(default)]
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted_type_predicates"
+    )]
     on_type_predicate: HashMap<String, HashSet<String>>,
+    /// Replace values of predicates when the object has a specific type
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted_type_predicates"
+    )]
+    on_object_type_predicate: HashMap<String, HashSet<String>>,
+    /// Replace values of predicates whose IRI matches a pattern.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    on_predicate_matching: Vec<Pattern>,
+    /// Replace literal values matching a pattern.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    on_literal_matching: Vec<Pattern>,
+    /// Replace objects satisfying all conditions of any entry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    on_object: Vec<ObjectCondition>,
+}
+
+/// Conditions on an object and its triple, all of which must hold.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectCondition {
+    /// Type of the subject.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    of_type: Option<String>,
+    /// Predicate of the triple.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    predicate: Option<String>,
+    /// Datatype of a literal object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    datatype: Option<String>,
+    /// Language range of a language-tagged literal object, e.g. `de` also matches `de-CH`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    /// Conditions on the value of a literal object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<ValueCondition>,
+}
+
+/// Conditions on a literal value, all of which must hold.
+/// Numeric comparisons never match values which are not numbers.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ValueCondition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    equals: Option<String>,
+    #[serde(default, rename = "in", skip_serializing_if = "Option::is_none")]
+    one_of: Option<HashSet<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gt: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ge: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lt: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    le: Option<f64>,
+    #[serde(default, with = "serde_regex", skip_serializing_if = "Option::is_none")]
+    regex: Option<Regex>,
+}
+
+impl ValueCondition {
+    /// Check if no condition is set, which matches any value.
+    fn is_empty(&self) -> bool {
+        self.equals.is_none()
+            && self.one_of.is_none()
+            && self.regex.is_none()
+            && [self.gt, self.ge, self.lt, self.le]
+                .iter()
+                .all(Option::is_none)
+    }
+
+    /// Check if the value satisfies all conditions.
+    pub fn is_match(&self, value: &str) -> bool {
+        if self.equals.as_ref().is_some_and(|e| e != value) {
+            return false;
+        }
+        if self.one_of.as_ref().is_some_and(|s| !s.contains(value)) {
+            return false;
+        }
+        if self.regex.as_ref().is_some_and(|r| !r.is_match(value)) {
+            return false;
+        }
+
+        let bounds = [self.gt, self.ge, self.lt, self.le];
+        if bounds.iter().all(Option::is_none) {
+            return true;
+        }
+        let Ok(number) = value.trim().parse::<f64>() else {
+            return false;
+        };
+        self.gt.map_or(true, |b| number > b)
+            && self.ge.map_or(true, |b| number >= b)
+            && self.lt.map_or(true, |b| number < b)
+            && self.le.map_or(true, |b| number <= b)
+    }
+}
+
+impl ObjectCondition {
+    /// URIs and CURIEs used in the condition.
+    fn uris(&self) -> impl Iterator<Item = &String> {
+        [&self.of_type, &self.predicate, &self.datatype]
+            .into_iter()
+            .flatten()
+    }
+
+    /// Reject conditions without any field, which would match all objects.
+    fn check_not_empty(&self) -> Result<(), anyhow::Error> {
+        if self.uris().next().is_none()
+            && self.language.is_none()
+            && self.value.as_ref().map_or(true, ValueCondition::is_empty)
+        {
+            return Err(anyhow!(
+                "objects.on_object: an entry must set at least one condition."
+            ));
+        }
+        Ok(())
+    }
+
+    /// Expand the cURIs of the condition given the prefixes provided
+    pub fn expand_curies(&self, prefixes: &PrefixMap) -> Result<ObjectCondition, anyhow::Error> {
+        self.check_not_empty()?;
+        let expand = |uri: &Option<String>| -> Result<Option<String>, anyhow::Error> {
+            match uri {
+                Some(u) => Ok(Some(
+                    Uri::try_from(u.clone())?.expand(prefixes)?.to_string(),
+                )),
+                None => Ok(None),
+            }
+        };
+
+        Ok(ObjectCondition {
+            of_type: expand(&self.of_type)?,
+            predicate: expand(&self.predicate)?,
+            datatype: expand(&self.datatype)?,
+            language: self.language.clone(),
+            value: self.value.clone(),
+        })
+    }
 }
 
 impl ObjectRules {
@@ -51,10 +300,18 @@ impl ObjectRules {
         let uris = UriSet::try_from(self.on_predicate.clone())?;
         uris.expand(prefixes)?;
 
-        for (k, v) in self.on_type_predicate.iter() {
+        for (k, v) in self
+            .on_type_predicate
+            .iter()
+            .chain(self.on_object_type_predicate.iter())
+        {
             Uri::try_from(k.clone())?.expand(prefixes)?;
             UriSet::try_from(v.clone())?.expand(prefixes)?;
         }
+        expand_iri_patterns(&self.on_predicate_matching, prefixes)?;
+        for c in self.on_object.iter() {
+            c.expand_curies(prefixes)?;
+        }
 
         Ok(())
     }
@@ -65,39 +322,651 @@ impl ObjectRules {
             .unwrap()
             .expand(prefixes)?;
 
-        let mut expanded_on_type_predicate = HashMap::<String, HashSet<String>>::new();
+        let expand_type_predicates = |type_predicates: &HashMap<String, HashSet<String>>| {
+            let mut expanded = HashMap::<String, HashSet<String>>::new();
+            for (k, v) in type_predicates.iter() {
+                let type_key = Uri::try_from(k.clone())?.expand(prefixes)?;
+                let pred_values = UriSet::try_from(v.clone())?.expand(prefixes)?;
+                expanded.insert(type_key.to_string(), pred_values.into());
+            }
+            Ok::<_, anyhow::Error>(expanded)
+        };
+
+        Ok(ObjectRules {
+            invert: self.invert,
+            on_predicate: expanded_on_predicate.into(),
+            on_type_predicate: expand_type_predicates(&self.on_type_predicate)?,
+            on_object_type_predicate: expand_type_predicates(&self.on_object_type_predicate)?,
+            on_predicate_matching: expand_iri_patterns(&self.on_predicate_matching, prefixes)?,
+            on_literal_matching: self.on_literal_matching.clone(),
+            on_object: self
+                .on_object
+                .iter()
+                .map(|c| c.expand_curies(prefixes))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Strategies to pseudonymize the IRI of a named node.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IriStrategy {
+    /// Keep the namespace up to the last `/`, `#` or `:`.
+    Namespace,
+    /// Replace the whole IRI by a pseudonym under the configured base namespace.
+    Base,
+    /// Keep only the scheme and authority of the IRI.
+    Authority,
+}
+
+/// Rules defining how the IRIs of named nodes are pseudonymized
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct IriRules {
+    /// Strategies tried in order until one can handle the IRI.
+    #[serde(default = "IriRules::default_strategies")]
+    pub strategies: Vec<IriStrategy>,
+    /// Namespace used by the `base` strategy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// Replacement namespaces for original namespaces of pseudonymized IRIs.
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted_map"
+    )]
+    pub namespaces: HashMap<String, String>,
+    /// Rewrite namespaces of all IRIs, not only pseudonymized ones.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rewrite_all: bool,
+    // Expanded namespaces without brackets, longest original namespace first.
+    #[serde(skip)]
+    pub(crate) rewrites: Vec<(String, String)>,
+}
+
+impl Default for IriRules {
+    fn default() -> Self {
+        IriRules {
+            strategies: Self::default_strategies(),
+            base: None,
+            namespaces: HashMap::new(),
+            rewrite_all: false,
+            rewrites: Vec::new(),
+        }
+    }
+}
+
+impl IriRules {
+    fn default_strategies() -> Vec<IriStrategy> {
+        vec![IriStrategy::Namespace]
+    }
+
+    /// Whether the rules keep the default behaviour, so that they can be
+    /// left out when serializing.
+    fn is_default(&self) -> bool {
+        self.strategies == Self::default_strategies()
+            && self.base.is_none()
+            && self.namespaces.is_empty()
+            && !self.rewrite_all
+    }
+
+    /// Validate the base namespace and ensure it is set when required.
+    pub fn check_uris(&self, prefixes: &PrefixMap) -> Result<(), anyhow::Error> {
+        if let Some(base) = &self.base {
+            Uri::try_from(base.clone())?.expand(prefixes)?;
+        } else if self.strategies.contains(&IriStrategy::Base) {
+            return Err(anyhow!(
+                "The 'base' IRI strategy requires 'iris.base' to be set."
+            ));
+        }
+        for (k, v) in self.namespaces.iter() {
+            Uri::try_from(k.clone())?.expand(prefixes)?;
+            Uri::try_from(v.clone())?.expand(prefixes)?;
+        }
+        Ok(())
+    }
+
+    /// Expand the base and rewritten namespaces given the prefixes provided
+    pub fn expand_curies(&self, prefixes: &PrefixMap) -> Result<IriRules, anyhow::Error> {
+        let base = match &self.base {
+            Some(b) => Some(Uri::try_from(b.clone())?.expand(prefixes)?.to_string()),
+            None => None,
+        };
+
+        let mut namespaces = HashMap::<String, String>::new();
+        for (k, v) in self.namespaces.iter() {
+            let from = Uri::try_from(k.clone())?.expand(prefixes)?;
+            let to = Uri::try_from(v.clone())?.expand(prefixes)?;
+            namespaces.insert(from.to_string(), to.to_string());
+        }
+
+        let mut rewrites: Vec<_> = namespaces
+            .iter()
+            .map(|(from, to)| {
+                (
+                    strip_brackets(from).to_string(),
+                    strip_brackets(to).to_string(),
+                )
+            })
+            .collect();
+        rewrites.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+
+        Ok(IriRules {
+            strategies: self.strategies.clone(),
+            base,
+            namespaces,
+            rewrite_all: self.rewrite_all,
+            rewrites,
+        })
+    }
+
+    /// Replace the longest matching original namespace of the IRI by its
+    /// replacement namespace. IRIs without a matching namespace are returned as-is.
+    /// Only namespaces of expanded rules are rewritten.
+    pub fn rewrite_namespace(&self, iri: &str) -> String {
+        let matched = self
+            .rewrites
+            .iter()
+            .find(|(from, _)| iri.starts_with(from.as_str()));
+
+        match matched {
+            Some((from, to)) => format!("{to}{}", &iri[from.len()..]),
+            None => iri.to_string(),
+        }
+    }
+}
+
+/// Remove the angle brackets around an expanded URI.
+pub fn strip_brackets(uri: &str) -> &str {
+    uri.trim_start_matches('<').trim_end_matches('>')
+}
+
+/// Exceptions overriding all other rules
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ExceptionRules {
+    /// Never replace nodes with a certain type.
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "sorted_set"
+    )]
+    of_type: HashSet<String>,
+    /// Never replace values of these predicates.
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "sorted_set"
+    )]
+    on_predicate: HashSet<String>,
+    /// Never replace values of predicates for specific types.
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted_type_predicates"
+    )]
+    on_type_predicate: HashMap<String, HashSet<String>>,
+    /// Never replace nodes whose IRI matches a pattern.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    matching: Vec<Pattern>,
+}
+
+impl ExceptionRules {
+    /// Whether no exception is listed.
+    fn is_empty(&self) -> bool {
+        self.of_type.is_empty()
+            && self.on_predicate.is_empty()
+            && self.on_type_predicate.is_empty()
+            && self.matching.is_empty()
+    }
+
+    /// Validate all URIs and CURIEs in exceptions and ensure they can
+    /// be expanded with the provided prefix map.
+    pub fn check_uris(&self, prefixes: &PrefixMap) -> Result<(), anyhow::Error> {
+        self.expand_curies(prefixes)?;
+        Ok(())
+    }
+
+    /// Expand the cURIs of the exceptions given the prefixes provided
+    pub fn expand_curies(&self, prefixes: &PrefixMap) -> Result<ExceptionRules, anyhow::Error> {
+        let mut on_type_predicate = HashMap::<String, HashSet<String>>::new();
         for (k, v) in self.on_type_predicate.iter() {
             let type_key = Uri::try_from(k.clone())?.expand(prefixes)?;
             let pred_values = UriSet::try_from(v.clone())?.expand(prefixes)?;
-            expanded_on_type_predicate.insert(type_key.to_string(), pred_values.into());
+            on_type_predicate.insert(type_key.to_string(), pred_values.into());
         }
 
-        Ok(ObjectRules {
-            on_predicate: expanded_on_predicate.into(),
-            on_type_predicate: expanded_on_type_predicate,
+        Ok(ExceptionRules {
+            of_type: UriSet::try_from(self.of_type.clone())?
+                .expand(prefixes)?
+                .into(),
+            on_predicate: UriSet::try_from(self.on_predicate.clone())?
+                .expand(prefixes)?
+                .into(),
+            on_type_predicate,
+            matching: expand_iri_patterns(&self.matching, prefixes)?,
+        })
+    }
+}
+
+/// Serialize prefixes with string keys, as required by json and toml.
+/// CURIEs always contain a colon, so the default prefix is only usable
+/// as the empty prefix and is written as such.
+fn serialize_prefixes<S: serde::Serializer>(
+    prefixes: &Option<HashMap<Option<String>, String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    prefixes
+        .as_ref()
+        .map(|p| {
+            p.iter()
+                .map(|(k, v)| (k.as_deref().unwrap_or_default(), v))
+                .collect::<BTreeMap<_, _>>()
         })
+        .serialize(serializer)
+}
+
+/// Deserialize prefixes from string keys, as found in json and toml,
+/// or a null key for the default prefix in yaml.
+fn deserialize_prefixes<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<HashMap<Option<String>, String>>, D::Error> {
+    // Keys are not deserialized as options by json and toml
+    #[derive(PartialEq, Eq, Hash)]
+    struct PrefixName(Option<String>);
+
+    impl<'de> Deserialize<'de> for PrefixName {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct NameVisitor;
+
+            impl serde::de::Visitor<'_> for NameVisitor {
+                type Value = PrefixName;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("a prefix name")
+                }
+
+                fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<PrefixName, E> {
+                    Ok(PrefixName(Some(v.to_string())))
+                }
+
+                fn visit_unit<E: serde::de::Error>(self) -> Result<PrefixName, E> {
+                    Ok(PrefixName(None))
+                }
+
+                fn visit_none<E: serde::de::Error>(self) -> Result<PrefixName, E> {
+                    Ok(PrefixName(None))
+                }
+            }
+
+            deserializer.deserialize_any(NameVisitor)
+        }
     }
+
+    let prefixes = Option::<HashMap<PrefixName, String>>::deserialize(deserializer)?;
+    Ok(prefixes.map(|p| p.into_iter().map(|(k, v)| (k.0, v)).collect()))
+}
+
+/// Serialize a set in sorted order, for a stable output.
+fn sorted_set<S: serde::Serializer>(
+    set: &HashSet<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    set.iter().collect::<BTreeSet<_>>().serialize(serializer)
+}
+
+/// Serialize a map in key order, for a stable output.
+fn sorted_map<S: serde::Serializer>(
+    map: &HashMap<String, String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// Serialize predicates by type in sorted order, for a stable output.
+fn sorted_type_predicates<S: serde::Serializer>(
+    map: &HashMap<String, HashSet<String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter()
+        .map(|(t, preds)| (t, preds.iter().collect::<BTreeSet<_>>()))
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+/// Kind of entity referred to by a URI of the rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UriKind {
+    Type,
+    Predicate,
+    Datatype,
+    Namespace,
+}
+
+/// A URI or CURIE listed in the rules, with the field listing it.
+#[derive(Debug, Clone)]
+pub struct UriEntry<'a> {
+    pub field: &'static str,
+    pub kind: UriKind,
+    pub uri: &'a str,
 }
 
 /// Rules for pseudonymizing triples
 #[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Rules {
-    // Invert all matchings
-    #[serde(default)]
+    // Rule files merged before this one, relative to this file.
+    // Only used when loading rules, see `io::parse_rules`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<PathBuf>,
+
+    // Invert all matchings. Deprecated: equivalent to
+    // setting `invert` in both `nodes` and `objects`.
+    // Predicates are never pseudonymized, so they have no section to invert.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub invert: bool,
 
-    #[serde(default)]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_prefixes",
+        deserialize_with = "deserialize_prefixes"
+    )]
     prefixes: Option<HashMap<Option<String>, String>>,
 
+    // Well-known prefixes used without declaring them, e.g. `foaf`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    use_prefixes: Vec<String>,
+
+    // Turtle files whose `@prefix` declarations are used.
+    // Only used when loading rules, see `io::parse_rules`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    prefixes_from: Vec<PathBuf>,
+
     #[serde(default)]
-    pub nodes: NodeRules,
+    pub(crate) nodes: NodeRules,
 
     #[serde(default)]
-    pub objects: ObjectRules,
+    pub(crate) objects: ObjectRules,
+
+    #[serde(default, skip_serializing_if = "IriRules::is_default")]
+    pub(crate) iris: IriRules,
+
+    // Built-in detectors scanning literal values
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) detectors: Vec<Detector>,
+
+    // Only replace the detected or regex-matched spans inside literals
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mask_spans: bool,
+
+    // Exceptions applied after all other matchings
+    #[serde(default, skip_serializing_if = "ExceptionRules::is_empty")]
+    pub(crate) exceptions: ExceptionRules,
 }
 
 /// Check if rules are setup correctly
 impl Rules {
+    /// Propagations of types along property paths, applied when indexing.
+    pub fn propagations(&self) -> &[Propagation] {
+        &self.nodes.propagate
+    }
+
+    /// Whether node rules are inverted, either directly or
+    /// through the deprecated global `invert` flag.
+    pub fn invert_nodes(&self) -> bool {
+        self.invert || self.nodes.invert
+    }
+
+    /// Whether object rules are inverted, either directly or
+    /// through the deprecated global `invert` flag.
+    pub fn invert_objects(&self) -> bool {
+        self.invert || self.objects.invert
+    }
+
+    /// Pseudonymize nodes of the given type.
+    pub fn add_node_type(&mut self, typ: String) {
+        self.nodes.of_type.insert(typ);
+    }
+
+    /// Pseudonymize values of the predicate for instances of the given type.
+    pub fn add_type_predicate(&mut self, typ: String, predicate: String) {
+        self.objects
+            .on_type_predicate
+            .entry(typ)
+            .or_default()
+            .insert(predicate);
+    }
+
+    /// Well-known prefixes used by the rules.
+    pub fn use_prefixes(&self) -> &[String] {
+        &self.use_prefixes
+    }
+
+    /// Turtle files whose prefixes are used by the rules.
+    pub fn prefix_files(&self) -> &[PathBuf] {
+        &self.prefixes_from
+    }
+
+    /// Add the well-known prefixes listed in `use_prefixes` and the given
+    /// prefixes, e.g. read from turtle files, to the prefixes of the rules.
+    /// Prefixes declared in the rules take precedence over imported ones,
+    /// and the given prefixes over well-known ones.
+    pub fn import_prefixes(
+        &mut self,
+        namespaces: HashMap<String, String>,
+    ) -> Result<(), anyhow::Error> {
+        let mut imported = HashMap::new();
+        for name in self.use_prefixes.iter() {
+            let Some(namespace) = known_prefix(name) else {
+                let known: Vec<_> = KNOWN_PREFIXES.iter().map(|(p, _)| *p).collect();
+                return Err(anyhow!(
+                    "Unknown prefix '{name}' in use_prefixes, known prefixes are: {}",
+                    known.join(", ")
+                ));
+            };
+            imported.insert(Some(name.clone()), format!("<{namespace}>"));
+        }
+        imported.extend(
+            namespaces
+                .into_iter()
+                .map(|(prefix, namespace)| (Some(prefix), format!("<{namespace}>"))),
+        );
+
+        if !imported.is_empty() {
+            let prefixes = self.prefixes.get_or_insert_with(HashMap::new);
+            for (prefix, namespace) in imported {
+                prefixes.entry(prefix).or_insert(namespace);
+            }
+        }
+        Ok(())
+    }
+
+    /// Rule files included by the rules, relative to the rules file.
+    pub fn includes(&self) -> &[PathBuf] {
+        &self.include
+    }
+
+    /// Prefixes declared in the rules.
+    pub fn prefixes(&self) -> Option<&HashMap<Option<String>, String>> {
+        self.prefixes.as_ref()
+    }
+
+    /// All URIs and CURIEs listed in the rules, in the order of the sections.
+    pub fn uri_entries(&self) -> Vec<UriEntry<'_>> {
+        fn list<'a>(
+            field: &'static str,
+            kind: UriKind,
+            uris: impl IntoIterator<Item = &'a String>,
+        ) -> impl Iterator<Item = UriEntry<'a>> {
+            uris.into_iter()
+                .map(move |uri| UriEntry { field, kind, uri })
+        }
+        fn list_type_predicates<'a>(
+            field: &'static str,
+            map: &'a HashMap<String, HashSet<String>>,
+        ) -> impl Iterator<Item = UriEntry<'a>> {
+            map.iter().flat_map(move |(k, v)| {
+                list(field, UriKind::Type, [k]).chain(list(field, UriKind::Predicate, v))
+            })
+        }
+
+        let (nodes, objects, exceptions) = (&self.nodes, &self.objects, &self.exceptions);
+        let mut entries: Vec<UriEntry> = Vec::new();
+        entries.extend(list("nodes.of_type", UriKind::Type, &nodes.of_type));
+        entries.extend(list(
+            "nodes.with_object_type",
+            UriKind::Type,
+            &nodes.with_object_type,
+        ));
+        for p in nodes.propagate.iter() {
+            entries.extend(list(
+                "nodes.propagate",
+                UriKind::Type,
+                [&p.from_type, &p.as_type],
+            ));
+            entries.extend(list("nodes.propagate", UriKind::Predicate, &p.path));
+        }
+        entries.extend(list(
+            "objects.on_predicate",
+            UriKind::Predicate,
+            &objects.on_predicate,
+        ));
+        entries.extend(list_type_predicates(
+            "objects.on_type_predicate",
+            &objects.on_type_predicate,
+        ));
+        entries.extend(list_type_predicates(
+            "objects.on_object_type_predicate",
+            &objects.on_object_type_predicate,
+        ));
+        for c in objects.on_object.iter() {
+            entries.extend(list("objects.on_object", UriKind::Type, &c.of_type));
+            entries.extend(list("objects.on_object", UriKind::Predicate, &c.predicate));
+            entries.extend(list("objects.on_object", UriKind::Datatype, &c.datatype));
+        }
+        entries.extend(list("iris.base", UriKind::Namespace, &self.iris.base));
+        for (k, v) in self.iris.namespaces.iter() {
+            entries.extend(list("iris.namespaces", UriKind::Namespace, [k, v]));
+        }
+        entries.extend(list(
+            "exceptions.of_type",
+            UriKind::Type,
+            &exceptions.of_type,
+        ));
+        entries.extend(list(
+            "exceptions.on_predicate",
+            UriKind::Predicate,
+            &exceptions.on_predicate,
+        ));
+        entries.extend(list_type_predicates(
+            "exceptions.on_type_predicate",
+            &exceptions.on_type_predicate,
+        ));
+        entries
+    }
+
+    /// All entries of rules which can match a triple, with the values
+    /// reported when explaining matches.
+    pub fn rule_entries(&self) -> Vec<RuleEntry> {
+        fn list<'a>(
+            rule: &'static str,
+            values: impl IntoIterator<Item = String> + 'a,
+        ) -> impl Iterator<Item = RuleEntry> + 'a {
+            values.into_iter().map(move |v| (rule, None, v))
+        }
+        fn list_type_predicates<'a>(
+            rule: &'static str,
+            map: &'a HashMap<String, HashSet<String>>,
+        ) -> impl Iterator<Item = RuleEntry> + 'a {
+            map.iter().flat_map(move |(t, preds)| {
+                preds
+                    .iter()
+                    .map(move |p| (rule, Some(t.clone()), p.clone()))
+            })
+        }
+        let patterns = |patterns: &[Pattern]| -> Vec<String> {
+            patterns.iter().map(Pattern::to_string).collect()
+        };
+
+        let (nodes, objects, exceptions) = (&self.nodes, &self.objects, &self.exceptions);
+        let mut entries: Vec<RuleEntry> = Vec::new();
+        entries.extend(list("nodes.of_type", nodes.of_type.iter().cloned()));
+        entries.extend(list("nodes.matching", patterns(&nodes.matching)));
+        entries.extend(list(
+            "nodes.with_object_type",
+            nodes.with_object_type.iter().cloned(),
+        ));
+        if self.invert_nodes() {
+            entries.push(("nodes.invert", None, "true".into()));
+        }
+        entries.extend(list(
+            "objects.on_predicate",
+            objects.on_predicate.iter().cloned(),
+        ));
+        entries.extend(list(
+            "objects.on_predicate_matching",
+            patterns(&objects.on_predicate_matching),
+        ));
+        entries.extend(list(
+            "objects.on_literal_matching",
+            patterns(&objects.on_literal_matching),
+        ));
+        entries.extend(list(
+            "objects.on_object",
+            objects
+                .on_object
+                .iter()
+                .map(|c| serde_json::to_string(c).unwrap_or_default()),
+        ));
+        entries.extend(list_type_predicates(
+            "objects.on_type_predicate",
+            &objects.on_type_predicate,
+        ));
+        entries.extend(list_type_predicates(
+            "objects.on_object_type_predicate",
+            &objects.on_object_type_predicate,
+        ));
+        if self.invert_objects() {
+            entries.push(("objects.invert", None, "true".into()));
+        }
+        entries.extend(list(
+            "detectors",
+            self.detectors.iter().map(|d| d.name().to_string()),
+        ));
+        entries.extend(list(
+            "exceptions.of_type",
+            exceptions.of_type.iter().cloned(),
+        ));
+        entries.extend(list(
+            "exceptions.on_predicate",
+            exceptions.on_predicate.iter().cloned(),
+        ));
+        entries.extend(list_type_predicates(
+            "exceptions.on_type_predicate",
+            &exceptions.on_type_predicate,
+        ));
+        entries.extend(list("exceptions.matching", patterns(&exceptions.matching)));
+        entries
+    }
+
+    /// All IRI patterns listed in the rules, with the field listing them.
+    pub fn iri_patterns(&self) -> Vec<(&'static str, &Pattern)> {
+        let fields = [
+            ("nodes.matching", &self.nodes.matching),
+            (
+                "objects.on_predicate_matching",
+                &self.objects.on_predicate_matching,
+            ),
+            ("exceptions.matching", &self.exceptions.matching),
+        ];
+        fields
+            .into_iter()
+            .flat_map(|(field, patterns)| patterns.iter().map(move |p| (field, p)))
+            .collect()
+    }
+
     pub fn check_uris(&self) -> Result<(), anyhow::Error> {
         // If prefixes are set, build prefix map, try expanding
         // and check both compact URIs and full URIs
@@ -106,15 +975,38 @@ impl Rules {
             let prefix_map = PrefixMap::from_hashmap(&self.prefixes.clone().unwrap())?;
             self.nodes.check_uris(&prefix_map).map_err(Error::from)?;
             self.objects.check_uris(&prefix_map).map_err(Error::from)?;
+            self.iris.check_uris(&prefix_map)?;
+            self.exceptions.check_uris(&prefix_map)?;
 
         // If no prefix are set, check each URI for validity
         } else {
             UriSet::try_from(self.nodes.of_type.clone())?;
+            UriSet::try_from(self.nodes.with_object_type.clone())?;
             UriSet::try_from(self.objects.on_predicate.clone())?;
-            for (k, v) in self.objects.on_type_predicate.iter() {
+            for (k, v) in self
+                .objects
+                .on_type_predicate
+                .iter()
+                .chain(self.objects.on_object_type_predicate.iter())
+            {
                 Uri::try_from(k.clone())?;
                 UriSet::try_from(v.clone())?;
             }
+            for c in self.objects.on_object.iter() {
+                c.check_not_empty()?;
+                for uri in c.uris() {
+                    Uri::try_from(uri.clone())?;
+                }
+            }
+            for p in self.nodes.propagate.iter() {
+                for uri in p.uris() {
+                    Uri::try_from(uri.clone())?;
+                }
+            }
+            expand_iri_patterns(&self.nodes.matching, &PrefixMap::new())?;
+            expand_iri_patterns(&self.objects.on_predicate_matching, &PrefixMap::new())?;
+            self.iris.check_uris(&PrefixMap::new())?;
+            self.exceptions.check_uris(&PrefixMap::new())?;
         };
         Ok(())
     }
@@ -123,132 +1015,682 @@ impl Rules {
         self.check_uris()?;
         match self.prefixes.as_ref() {
             // If there's no prefixes return Rules as they are
-            None => {
-                return Ok(Rules {
-                    invert: self.invert,
-                    prefixes: self.prefixes.clone(),
-                    nodes: NodeRules {
-                        of_type: self.nodes.of_type.clone(),
-                    },
-                    objects: ObjectRules {
-                        on_predicate: self.objects.on_predicate.clone(),
-                        on_type_predicate: self.objects.on_type_predicate.clone(),
-                    },
-                });
-            }
+            None => Ok(Rules {
+                include: Vec::new(),
+                invert: self.invert,
+                prefixes: self.prefixes.clone(),
+                use_prefixes: self.use_prefixes.clone(),
+                prefixes_from: self.prefixes_from.clone(),
+                nodes: NodeRules {
+                    matching: expand_iri_patterns(&self.nodes.matching, &PrefixMap::new())?,
+                    ..self.nodes.clone()
+                },
+                objects: ObjectRules {
+                    on_predicate_matching: expand_iri_patterns(
+                        &self.objects.on_predicate_matching,
+                        &PrefixMap::new(),
+                    )?,
+                    ..self.objects.clone()
+                },
+                iris: self.iris.expand_curies(&PrefixMap::new())?,
+                detectors: self.detectors.clone(),
+                mask_spans: self.mask_spans,
+                exceptions: self.exceptions.expand_curies(&PrefixMap::new())?,
+            }),
             // If there's prefixes, return expanded cURIs and full URIs
             Some(p) => {
                 let prefix_map = PrefixMap::from_hashmap(p)?;
-                return Ok(Rules {
+                Ok(Rules {
+                    include: Vec::new(),
                     invert: self.invert,
                     prefixes: self.prefixes.clone(),
+                    use_prefixes: self.use_prefixes.clone(),
+                    prefixes_from: self.prefixes_from.clone(),
                     nodes: self.nodes.expand_curies(&prefix_map)?,
                     objects: self.objects.expand_curies(&prefix_map)?,
-                });
+                    iris: self.iris.expand_curies(&prefix_map)?,
+                    detectors: self.detectors.clone(),
+                    mask_spans: self.mask_spans,
+                    exceptions: self.exceptions.expand_curies(&prefix_map)?,
+                })
             }
         }
     }
 }
 
+/// Add a suggestion for the closest known field to errors about an
+/// unknown field in the rules, e.g. a typo such as `on_predicates`.
+pub fn suggest_field(message: &str) -> String {
+    let Some((head, rest)) = message.split_once("unknown field `") else {
+        return message.to_string();
+    };
+    let Some((unknown, rest)) = rest.split_once('`') else {
+        return message.to_string();
+    };
+
+    // Known fields are listed in backticks after the unknown one
+    let suggestion = rest
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|known| (strsim::jaro_winkler(unknown, known), known))
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+
+    match suggestion {
+        Some((_, known)) => {
+            format!("{head}unknown field `{unknown}` (did you mean `{known}`?){rest}")
+        }
+        None => message.to_string(),
+    }
+}
+
+/// Part of a triple which a rule applies to.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Part {
+    Subject,
+    Object,
+}
+
+impl Part {
+    fn mask(&self) -> TripleMask {
+        match self {
+            Part::Subject => TripleMask::SUBJECT,
+            Part::Object => TripleMask::OBJECT,
+        }
+    }
+}
+
+/// Effect of a matched rule on the part of the triple.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    /// The part is pseudonymized.
+    Pseudonymize,
+    /// Only matched spans inside the literal are pseudonymized.
+    PseudonymizeSpans,
+    /// The part, or its spans, is kept by an exception or an inverted section.
+    Keep,
+}
+
+/// A rule which matched a part of a triple.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RuleMatch {
+    pub part: Part,
+    /// Section and field of the rule, e.g. `nodes.of_type`.
+    pub rule: &'static str,
+    /// Entry of the rule which matched.
+    pub value: String,
+    /// Type from the index which caused the match.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_type: Option<String>,
+    pub effect: Effect,
+}
+
+impl RuleMatch {
+    /// Entry of the rule which matched, as listed by [`Rules::rule_entries`].
+    pub fn entry(&self) -> RuleEntry {
+        let of_type = match self.rule {
+            "objects.on_type_predicate"
+            | "objects.on_object_type_predicate"
+            | "exceptions.on_type_predicate" => self.index_type.clone(),
+            _ => None,
+        };
+        (self.rule, of_type, self.value.clone())
+    }
+}
+
+/// Entry of a rule: its section and field, the type of type-predicate
+/// rules, and the value reported when it matches.
+pub type RuleEntry = (&'static str, Option<String>, String);
+
+/// All rules matching a triple, and the resulting mask.
+#[derive(Debug)]
+pub struct Explanation {
+    pub mask: TripleMask,
+    pub matches: Vec<RuleMatch>,
+}
+
 /// Check all parts of the triple against rules.
-pub fn match_rules(triple: &Triple, rules: &Rules, type_map: &mut TypeIndex) -> TripleMask {
-    let mut mask =
-        match_node_rules(triple, rules, type_map) | match_object_rules(triple, rules, type_map);
+/// A part is replaced when a non-inverted section matches it, or when it is
+/// covered by inverted sections and none of them matches it.
+pub fn match_rules(triple: &Triple, rules: &Rules, type_map: &TypeIndex) -> TripleMask {
+    evaluate_rules(triple, rules, type_map, false).mask
+}
+
+/// Check all parts of the triple against every rule, without stopping at
+/// the first match, and report which rules matched and why.
+/// The resulting mask is the one of [`match_rules`].
+pub fn explain_rules(triple: &Triple, rules: &Rules, type_map: &TypeIndex) -> Explanation {
+    evaluate_rules(triple, rules, type_map, true)
+}
+
+/// Rules matched while evaluating a triple. Matches are folded into the
+/// mask of their section, and only recorded in full when explaining.
+#[derive(Default)]
+struct Evaluation {
+    explain: bool,
+    nodes: TripleMask,
+    objects: TripleMask,
+    exceptions: TripleMask,
+    matches: Vec<RuleMatch>,
+}
+
+impl Evaluation {
+    fn push(
+        &mut self,
+        part: Part,
+        rule: &'static str,
+        value: impl FnOnce() -> String,
+        index_type: Option<String>,
+        effect: Effect,
+    ) {
+        // Spans are masked in place, not by the mask of the triple
+        if effect != Effect::PseudonymizeSpans {
+            let section = match rule.split('.').next() {
+                Some("nodes") => &mut self.nodes,
+                Some("exceptions") => &mut self.exceptions,
+                _ => &mut self.objects,
+            };
+            *section |= part.mask();
+        }
+        if self.explain {
+            self.matches.push(RuleMatch {
+                part,
+                rule,
+                value: value(),
+                index_type,
+                effect,
+            });
+        }
+    }
+}
+
+/// Evaluate all rules against the triple and combine their matches.
+fn evaluate_rules(
+    triple: &Triple,
+    rules: &Rules,
+    type_map: &TypeIndex,
+    explain: bool,
+) -> Explanation {
+    let mut eval = Evaluation {
+        explain,
+        ..Default::default()
+    };
+    evaluate_node_rules(triple, rules, type_map, &mut eval);
+    evaluate_object_rules(triple, rules, type_map, &mut eval);
+    evaluate_exception_rules(triple, rules, type_map, &mut eval);
+
+    let (mask, inverted) = combine_sections(triple, rules, eval.nodes, eval.objects);
+
+    if explain {
+        // Parts replaced because an inverted section does not match them
+        for part in [Part::Subject, Part::Object] {
+            if !inverted.is_set(&part.mask()) {
+                continue;
+            }
+            if rules.invert_nodes() && !eval.nodes.is_set(&part.mask()) {
+                eval.push(
+                    part,
+                    "nodes.invert",
+                    || "true".into(),
+                    None,
+                    Effect::Pseudonymize,
+                );
+            }
+            if rules.invert_objects() && part == Part::Object {
+                eval.push(
+                    part,
+                    "objects.invert",
+                    || "true".into(),
+                    None,
+                    Effect::Pseudonymize,
+                );
+            }
+        }
+        // Excepted objects are not masked in place either
+        if eval.exceptions.is_set(&TripleMask::OBJECT) {
+            for m in eval.matches.iter_mut() {
+                if m.part == Part::Object && m.effect == Effect::PseudonymizeSpans {
+                    m.effect = Effect::Keep;
+                }
+            }
+        }
+    }
 
-    if rules.invert {
-        mask = mask.invert();
+    Explanation {
+        mask: mask - eval.exceptions,
+        matches: eval.matches,
+    }
+}
+
+/// Combine the masks matched by node and object rules according to the
+/// inversion of each section. Also returns the parts replaced because
+/// inverted sections cover them without matching.
+fn combine_sections(
+    triple: &Triple,
+    rules: &Rules,
+    node_mask: TripleMask,
+    object_mask: TripleMask,
+) -> (TripleMask, TripleMask) {
+    let mut mask = TripleMask::default();
+    let mut covered = TripleMask::default();
+    let mut excluded = TripleMask::default();
+
+    if rules.invert_nodes() {
+        // Node rules only cover the subject and objects which are nodes
+        covered |= TripleMask::SUBJECT;
+        if !matches!(triple.object, Term::Literal(_)) {
+            covered |= TripleMask::OBJECT;
+        }
+        excluded |= node_mask;
+    } else {
+        mask |= node_mask;
+    }
+
+    if rules.invert_objects() {
+        covered |= TripleMask::OBJECT;
+        excluded |= object_mask;
+    } else {
+        mask |= object_mask;
+    }
+
+    let inverted = covered & excluded.invert();
+    (mask | inverted, inverted)
+}
+
+/// Check triple against node-pseudonymization rules.
+fn evaluate_node_rules(
+    triple: &Triple,
+    rules: &Rules,
+    type_map: &TypeIndex,
+    eval: &mut Evaluation,
+) {
+    let effect = if rules.invert_nodes() {
+        Effect::Keep
+    } else {
+        Effect::Pseudonymize
+    };
+    let nodes = &rules.nodes;
+    if let Subject::NamedNode(n) = &triple.subject {
+        if let Some(t) = find_type(&n.to_string(), &nodes.of_type, type_map) {
+            eval.push(
+                Part::Subject,
+                "nodes.of_type",
+                || t.clone(),
+                Some(t.clone()),
+                effect,
+            );
+        }
+        if let Some(p) = find_pattern(&nodes.matching, n.iri) {
+            eval.push(
+                Part::Subject,
+                "nodes.matching",
+                || p.to_string(),
+                None,
+                effect,
+            );
+        }
+        if let Some((key, _)) = object_key(&triple.object) {
+            if let Some(t) = find_type(&key, &nodes.with_object_type, type_map) {
+                let value = || t.clone();
+                eval.push(
+                    Part::Subject,
+                    "nodes.with_object_type",
+                    value,
+                    Some(t.clone()),
+                    effect,
+                );
+            }
+        }
+    }
+    if let Term::NamedNode(n) = &triple.object {
+        if let Some(t) = find_type(&n.to_string(), &nodes.of_type, type_map) {
+            eval.push(
+                Part::Object,
+                "nodes.of_type",
+                || t.clone(),
+                Some(t.clone()),
+                effect,
+            );
+        }
+        if let Some(p) = find_pattern(&nodes.matching, n.iri) {
+            eval.push(
+                Part::Object,
+                "nodes.matching",
+                || p.to_string(),
+                None,
+                effect,
+            );
+        }
+    }
+}
+
+/// Checks triple against object-pseudonymization rules and detectors.
+fn evaluate_object_rules(
+    triple: &Triple,
+    rules: &Rules,
+    type_map: &TypeIndex,
+    eval: &mut Evaluation,
+) {
+    let effect = if rules.invert_objects() {
+        Effect::Keep
+    } else {
+        Effect::Pseudonymize
+    };
+    let objects = &rules.objects;
+    let predicate = triple.predicate.to_string();
+    if objects.on_predicate.contains(&predicate) {
+        let value = || predicate.clone();
+        eval.push(Part::Object, "objects.on_predicate", value, None, effect);
+    }
+    if let Some(p) = find_pattern(&objects.on_predicate_matching, triple.predicate.iri) {
+        let value = || p.to_string();
+        eval.push(
+            Part::Object,
+            "objects.on_predicate_matching",
+            value,
+            None,
+            effect,
+        );
+    }
+    if let Term::Literal(l) = &triple.object {
+        let value = literal_value(l);
+        for p in objects
+            .on_literal_matching
+            .iter()
+            .filter(|p| p.is_match(value))
+        {
+            // Regex matches are masked in place with `mask_spans`
+            let effect = match p {
+                Pattern::Regex(_) if rules.mask_spans => Effect::PseudonymizeSpans,
+                _ => effect,
+            };
+            let value = || p.to_string();
+            eval.push(
+                Part::Object,
+                "objects.on_literal_matching",
+                value,
+                None,
+                effect,
+            );
+        }
+        for d in rules.detectors.iter().filter(|d| d.is_match(value)) {
+            let effect = if rules.mask_spans {
+                Effect::PseudonymizeSpans
+            } else {
+                effect
+            };
+            eval.push(Part::Object, "detectors", || d.name().into(), None, effect);
+        }
+    }
+    for c in objects.on_object.iter() {
+        if match_object_condition(c, triple, type_map) {
+            let value = || serde_json::to_string(c).unwrap_or_default();
+            eval.push(Part::Object, "objects.on_object", value, None, effect);
+        }
+    }
+    let subject = subject_key(&triple.subject);
+    if let Some(t) = find_type_predicate(&subject, &predicate, &objects.on_type_predicate, type_map)
+    {
+        let value = || predicate.clone();
+        eval.push(
+            Part::Object,
+            "objects.on_type_predicate",
+            value,
+            Some(t),
+            effect,
+        );
+    }
+    if let Some((key, _)) = object_key(&triple.object) {
+        if let Some(t) = find_type_predicate(
+            &key,
+            &predicate,
+            &objects.on_object_type_predicate,
+            type_map,
+        ) {
+            let value = || predicate.clone();
+            let rule = "objects.on_object_type_predicate";
+            eval.push(Part::Object, rule, value, Some(t), effect);
+        }
+    }
+}
+
+/// Check triple against exceptions, which keep the parts they match.
+fn evaluate_exception_rules(
+    triple: &Triple,
+    rules: &Rules,
+    type_map: &TypeIndex,
+    eval: &mut Evaluation,
+) {
+    let exceptions = &rules.exceptions;
+    let subject = subject_key(&triple.subject);
+    let subject_iri = match &triple.subject {
+        Subject::NamedNode(n) => Some(n.iri),
+        _ => None,
+    };
+    let nodes = [
+        (Part::Subject, Some((subject.clone(), subject_iri))),
+        (Part::Object, object_key(&triple.object)),
+    ];
+    for (part, node) in nodes {
+        let Some((key, iri)) = node else {
+            continue;
+        };
+        if let Some(p) = iri.and_then(|iri| find_pattern(&exceptions.matching, iri)) {
+            eval.push(
+                part,
+                "exceptions.matching",
+                || p.to_string(),
+                None,
+                Effect::Keep,
+            );
+        }
+        if let Some(t) = find_type(&key, &exceptions.of_type, type_map) {
+            eval.push(
+                part,
+                "exceptions.of_type",
+                || t.clone(),
+                Some(t.clone()),
+                Effect::Keep,
+            );
+        }
+    }
+    let predicate = triple.predicate.to_string();
+    if exceptions.on_predicate.contains(&predicate) {
+        let value = || predicate.clone();
+        eval.push(
+            Part::Object,
+            "exceptions.on_predicate",
+            value,
+            None,
+            Effect::Keep,
+        );
+    }
+    if let Some(t) = find_type_predicate(
+        &subject,
+        &predicate,
+        &exceptions.on_type_predicate,
+        type_map,
+    ) {
+        let value = || predicate.clone();
+        eval.push(
+            Part::Object,
+            "exceptions.on_type_predicate",
+            value,
+            Some(t),
+            Effect::Keep,
+        );
+    }
+}
+
+/// Check triple against exceptions, returning the parts which must be kept.
+pub fn match_exception_rules(triple: &Triple, rules: &Rules, type_map: &TypeIndex) -> TripleMask {
+    let mut eval = Evaluation::default();
+    evaluate_exception_rules(triple, rules, type_map, &mut eval);
+    eval.exceptions
+}
+
+/// Find the byte ranges of a literal object to pseudonymize in place.
+/// Spans come from detectors and regex literal patterns and are only
+/// returned when `mask_spans` is enabled and the object is not excepted.
+/// Overlapping spans are merged.
+pub fn match_literal_spans(
+    triple: &Triple,
+    rules: &Rules,
+    type_map: &TypeIndex,
+) -> Vec<Range<usize>> {
+    let Term::Literal(literal) = &triple.object else {
+        return Vec::new();
+    };
+    if !rules.mask_spans
+        || match_exception_rules(triple, rules, type_map).is_set(&TripleMask::OBJECT)
+    {
+        return Vec::new();
+    }
+    let value = literal_value(literal);
+
+    let mut spans: Vec<Range<usize>> = rules
+        .detectors
+        .iter()
+        .flat_map(|d| d.find_spans(value))
+        .chain(
+            rules
+                .objects
+                .on_literal_matching
+                .iter()
+                .flat_map(|p| p.find_spans(value)),
+        )
+        .filter(|r| !r.is_empty())
+        .collect();
+    spans.sort_by_key(|r| r.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+/// Get the lexical form of a literal.
+fn literal_value<'a>(literal: &Literal<'a>) -> &'a str {
+    match literal {
+        Literal::Simple { value } => value,
+        Literal::LanguageTaggedString { value, .. } => value,
+        Literal::Typed { value, .. } => value,
+    }
+}
+
+/// Check if the triple satisfies all conditions of an object rule.
+fn match_object_condition(
+    condition: &ObjectCondition,
+    triple: &Triple,
+    type_map: &TypeIndex,
+) -> bool {
+    if let Some(predicate) = &condition.predicate {
+        if *predicate != triple.predicate.to_string() {
+            return false;
+        }
+    }
+
+    if let Some(datatype) = &condition.datatype {
+        let literal_datatype = match &triple.object {
+            Term::Literal(Literal::Simple { .. }) => format!("<{XSD_STRING}>"),
+            Term::Literal(Literal::LanguageTaggedString { .. }) => format!("<{RDF_LANG_STRING}>"),
+            Term::Literal(Literal::Typed { datatype, .. }) => datatype.to_string(),
+            _ => return false,
+        };
+        if *datatype != literal_datatype {
+            return false;
+        }
+    }
+
+    if let Some(range) = &condition.language {
+        let Term::Literal(Literal::LanguageTaggedString { language, .. }) = &triple.object else {
+            return false;
+        };
+        if !match_language(language, range) {
+            return false;
+        }
+    }
+
+    if let Some(value_condition) = &condition.value {
+        let Term::Literal(l) = &triple.object else {
+            return false;
+        };
+        if !value_condition.is_match(literal_value(l)) {
+            return false;
+        }
+    }
+
+    if let Some(typ) = &condition.of_type {
+        match type_map.get(&subject_key(&triple.subject)) {
+            Some(types) if types.contains(&typ.as_str()) => {}
+            _ => return false,
+        }
     }
 
-    mask
+    true
 }
 
-/// Check triple against node-pseudonymization rules.
-pub fn match_node_rules(triple: &Triple, rules: &Rules, type_map: &mut TypeIndex) -> TripleMask {
-    let pseudo_subject = match &triple.subject {
-        Subject::NamedNode(n) => match_type(&n.to_string(), rules, type_map),
-        Subject::BlankNode(_) => false,
-        Subject::Triple(_) => panic!("RDF-star data not supported"),
-    };
-    let pseudo_object = match &triple.object {
-        Term::NamedNode(n) => match_type(&n.to_string(), rules, type_map),
-        Term::BlankNode(_) => false,
-        Term::Literal(_) => false,
-        Term::Triple(_) => panic!("RDF-star data not supported"),
-    };
-
-    let mut mask = TripleMask::default();
-    if pseudo_subject {
-        mask |= TripleMask::SUBJECT;
-    };
-    if pseudo_object {
-        mask |= TripleMask::OBJECT;
-    };
-
-    mask
+/// Basic filtering of a language tag by a language range (RFC 4647),
+/// e.g. the range `de` matches `de` and `de-CH`.
+fn match_language(tag: &str, range: &str) -> bool {
+    let tag = tag.to_lowercase();
+    let range = range.to_lowercase();
+    range == "*" || tag == range || tag.starts_with(&format!("{range}-"))
 }
 
-/// Checks triple against object-pseudonymization rules
-pub fn match_object_rules(triple: &Triple, rules: &Rules, type_map: &mut TypeIndex) -> TripleMask {
-    if match_predicate(&triple.predicate.to_string(), rules) {
-        return TripleMask::OBJECT;
-    }
-
-    let pseudo_object = match &triple.subject {
-        Subject::NamedNode(n) => match_type_predicate(
-            &n.to_string(),
-            &triple.predicate.to_string(),
-            type_map,
-            rules,
-        ),
-        Subject::BlankNode(b) => match_type_predicate(
-            &b.to_string(),
-            &triple.predicate.to_string(),
-            type_map,
-            rules,
-        ),
+/// Get the index key of a subject.
+fn subject_key(subject: &Subject) -> String {
+    match subject {
+        Subject::NamedNode(n) => n.to_string(),
+        Subject::BlankNode(b) => b.to_string(),
         Subject::Triple(_) => panic!("RDF-star data not supported"),
-    };
-
-    if pseudo_object {
-        return TripleMask::OBJECT;
     }
-
-    TripleMask::default()
 }
 
-/// Check if the type of input instance URI is in the rules.
-fn match_type(subject: &str, rules: &Rules, type_map: &mut TypeIndex) -> bool {
-    if let Some(v) = type_map.get(subject) {
-        v.iter().any(|&i| rules.nodes.of_type.contains(i))
-    } else {
-        false
+/// Get the index key and IRI of an object which is a node.
+fn object_key<'a>(object: &Term<'a>) -> Option<(String, Option<&'a str>)> {
+    match object {
+        Term::NamedNode(n) => Some((n.to_string(), Some(n.iri))),
+        Term::BlankNode(b) => Some((b.to_string(), None)),
+        Term::Literal(_) => None,
+        Term::Triple(_) => panic!("RDF-star data not supported"),
     }
 }
 
-/// Check if the predicate URI is in the rules.
-fn match_predicate(predicate: &str, rules: &Rules) -> bool {
-    rules.objects.on_predicate.contains(predicate)
+/// Find a type of the node in the index which is in the given types.
+fn find_type(node: &str, types: &HashSet<String>, type_map: &TypeIndex) -> Option<String> {
+    type_map
+        .get(node)?
+        .into_iter()
+        .find(|&t| types.contains(t))
+        .map(String::from)
 }
 
-/// Check if the combination of subject type and predicate URIs is in the rules.
-fn match_type_predicate(
-    subject: &str,
-    predicate: &str,
-    type_map: &mut TypeIndex,
-    rules: &Rules,
-) -> bool {
-    let Some(instance_types) = type_map.get(subject) else {
-        return false;
-    };
+/// Find the first pattern matching the value.
+fn find_pattern<'a>(patterns: &'a [Pattern], value: &str) -> Option<&'a Pattern> {
+    patterns.iter().find(|p| p.is_match(value))
+}
 
-    for typ in instance_types {
-        if let Some(preds) = rules.objects.on_type_predicate.get(typ) {
-            if preds.contains(predicate) {
-                return true;
-            }
-        }
-    }
-    false
+/// Find a type of the node in the index for which the
+/// predicate is listed in the given rules.
+fn find_type_predicate(
+    node: &str,
+    predicate: &str,
+    rules: &HashMap<String, HashSet<String>>,
+    type_map: &TypeIndex,
+) -> Option<String> {
+    type_map
+        .get(node)?
+        .into_iter()
+        .find(|&t| rules.get(t).is_some_and(|preds| preds.contains(predicate)))
+        .map(String::from)
 }
 
 #[cfg(test)]
@@ -273,7 +1715,7 @@ mod tests {
             TypeIndex::from_iter(
                 vec![
                 $(($key, $value)),*
-            ].into_iter())
+            ])
         };
     }
 
@@ -281,6 +1723,21 @@ mod tests {
         serde_yml::from_str(yml).unwrap()
     }
 
+    /// Match the triple `NODE_IRI PREDICATE_IRI "Alice"` against the rules.
+    fn match_test_triple(rules: &Rules, index: &TypeIndex) -> TripleMask {
+        let triple = Triple {
+            subject: NamedNode {
+                iri: NODE_IRI.trim_matches(['<', '>']),
+            }
+            .into(),
+            predicate: NamedNode {
+                iri: PREDICATE_IRI.trim_matches(['<', '>']),
+            },
+            object: Literal::Simple { value: "Alice" }.into(),
+        };
+        match_rules(&triple, rules, index)
+    }
+
     #[rstest]
     // Subject is in the rules & type index
     #[case(index! { NODE_IRI => "<Person>" }, "<Person>", true)]
@@ -288,11 +1745,7 @@ mod tests {
     #[case(index! { NODE_IRI => "<Person>" }, "<Bank>", false)]
     // Subject is not in the type index
     #[case(index! { "<BankName>" => "<Bank>" }, "<Bank>", false)]
-    fn type_rule(
-        #[case] mut index: TypeIndex,
-        #[case] rule_type: &str,
-        #[case] match_expected: bool,
-    ) {
+    fn type_rule(#[case] index: TypeIndex, #[case] rule_type: &str, #[case] match_expected: bool) {
         let rules = parse_rules(&format!(
             "
             nodes:
@@ -301,7 +1754,8 @@ mod tests {
         "
         ));
 
-        assert_eq!(match_type(NODE_IRI, &rules, &mut index), match_expected);
+        let expected_mask = if match_expected { 0b100 } else { 0b000 };
+        assert_eq!(match_test_triple(&rules, &index).bits(), expected_mask);
     }
 
     #[rstest]
@@ -317,7 +1771,8 @@ mod tests {
               - {rule_predicate}
         "
         ));
-        assert_eq!(match_predicate(PREDICATE_IRI, &rules), match_expected);
+        let expected_mask = if match_expected { 0b001 } else { 0b000 };
+        assert_eq!(match_test_triple(&rules, &index! {}).bits(), expected_mask);
     }
 
     #[rstest]
@@ -332,7 +1787,7 @@ mod tests {
     fn type_predicate_rule(
         #[case] rule_type: &str,
         #[case] rule_predicate: &str,
-        #[case] mut index: TypeIndex,
+        #[case] index: TypeIndex,
         #[case] match_expected: bool,
     ) {
         let rules = parse_rules(&format!(
@@ -344,10 +1799,8 @@ mod tests {
         "
         ));
 
-        assert_eq!(
-            match_type_predicate(NODE_IRI, PREDICATE_IRI, &mut index, &rules),
-            match_expected
-        );
+        let expected_mask = if match_expected { 0b001 } else { 0b000 };
+        assert_eq!(match_test_triple(&rules, &index).bits(), expected_mask);
     }
 
     #[rstest]
@@ -373,7 +1826,7 @@ mod tests {
                 "<urn:Person>": ["<urn:hasAge>"]
             "#,
         );
-        let mut index = index! {
+        let index = index! {
             "<urn:Alice>" => "<urn:Person>",
             "<urn:Bob>" => "<urn:Person>",
             "<urn:ACME>" => "<urn:Organization>"
@@ -382,12 +1835,385 @@ mod tests {
         println!("{}", serde_json::to_string(&index).unwrap());
         TurtleParser::new(triple.as_ref(), None)
             .parse_all(&mut |t| {
-                let mask = match_rules(&t.into(), &rules, &mut index);
+                let mask = match_rules(&t, &rules, &index);
+                assert_eq!(mask.bits(), expected_mask);
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+    #[rstest]
+    // Subject IRI matches a node pattern
+    #[case(r#"<http://example.org/patients/1> <urn:hasAge> "42" ."#, 0b100)]
+    // Object IRI matches a node pattern
+    #[case(
+        r#"<urn:ACME> <urn:hasClient> <http://example.org/patients/1> ."#,
+        0b001
+    )]
+    // Predicate matches a namespace pattern
+    #[case(r#"<urn:ACME> <http://example.org/private/code> "1" ."#, 0b001)]
+    // Literal matches a regex pattern
+    #[case(r#"<urn:ACME> <urn:note> "AHV 756.1234.5678.97"@de ."#, 0b001)]
+    // Nothing matches
+    #[case(r#"<urn:ACME> <urn:note> "756" ."#, 0b000)]
+    fn pattern_rules(#[case] triple: &str, #[case] expected_mask: u8) {
+        let rules = parse_rules(
+            r#"
+            prefixes:
+              ex: <http://example.org/>
+            nodes:
+              matching:
+                - regex: '^http://example\.org/patients/\d+$'
+            objects:
+              on_predicate_matching:
+                - prefix: "ex:private/"
+              on_literal_matching:
+                - regex: '756\.\d{4}\.\d{4}\.\d{2}'
+            "#,
+        )
+        .expand_rules_curie()
+        .unwrap();
+        let index = index! {};
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let mask = match_rules(&t, &rules, &index);
+                assert_eq!(mask.bits(), expected_mask);
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[rstest]
+    // Enabled detector matches
+    #[case(r#"<urn:ACME> <urn:note> "Mail alice@example.org" ."#, 0b001)]
+    // Disabled detector does not match
+    #[case(r#"<urn:ACME> <urn:note> "Card 4111 1111 1111 1111" ."#, 0b000)]
+    // Detectors ignore named nodes
+    #[case(r#"<urn:ACME> <urn:note> <mailto:alice@example.org> ."#, 0b000)]
+    fn detector_rules(#[case] triple: &str, #[case] expected_mask: u8) {
+        let rules = parse_rules("detectors: [email, ahv]");
+        let index = index! {};
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let mask = match_rules(&t, &rules, &index);
+                assert_eq!(mask.bits(), expected_mask);
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[rstest]
+    // Detector span
+    #[case(r#"<urn:ACME> <urn:note> "Mail alice@example.org" ."#, vec![5..22])]
+    // Overlapping detector and regex spans are merged
+    #[case(r#"<urn:ACME> <urn:note> "alice@example.org or 756" ."#, vec![0..17, 21..24])]
+    // Prefix patterns mask the whole literal, empty regex matches are not spans
+    #[case(r#"<urn:ACME> <urn:note> "ID-1234" ."#, vec![])]
+    fn literal_spans(#[case] triple: &str, #[case] expected_spans: Vec<Range<usize>>) {
+        let rules = parse_rules(
+            r#"
+            mask_spans: true
+            detectors: [email]
+            objects:
+              on_literal_matching:
+                - regex: 'alice@example'
+                - regex: '756'
+                - regex: 'z*'
+                - prefix: 'ID-'
+            "#,
+        );
+        let index = index! {};
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let mask = match_rules(&t, &rules, &index);
+                let spans = match_literal_spans(&t, &rules, &index);
+                assert_eq!(mask.is_empty(), !expected_spans.is_empty());
+                assert_eq!(spans, expected_spans);
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[rstest]
+    // Datatype selector
+    #[case(
+        r#"<urn:ACME> <urn:founded> "1900-01-01"^^<http://www.w3.org/2001/XMLSchema#date> ."#,
+        0b001
+    )]
+    // Datatype selector, other datatype
+    #[case(
+        r#"<urn:ACME> <urn:founded> "1900"^^<http://www.w3.org/2001/XMLSchema#gYear> ."#,
+        0b000
+    )]
+    // Simple literals are xsd:string
+    #[case(r#"<urn:Alice> <urn:hasName> "Alice" ."#, 0b001)]
+    // Language selector with predicate, language subtag
+    #[case(
+        r#"<urn:ACME> <http://www.w3.org/2000/01/rdf-schema#comment> "Grüezi"@de-CH ."#,
+        0b001
+    )]
+    // Language selector with predicate, other predicate
+    #[case(r#"<urn:ACME> <urn:note> "Hallo"@de ."#, 0b000)]
+    // Language selector with predicate, other language
+    #[case(
+        r#"<urn:ACME> <http://www.w3.org/2000/01/rdf-schema#comment> "Hello"@en ."#,
+        0b000
+    )]
+    // String datatype on other subject type
+    #[case(r#"<urn:ACME> <urn:hasName> "ACME" ."#, 0b000)]
+    fn object_conditions(#[case] triple: &str, #[case] expected_mask: u8) {
+        let rules = parse_rules(
+            r#"
+            prefixes:
+              xsd: <http://www.w3.org/2001/XMLSchema#>
+              rdfs: <http://www.w3.org/2000/01/rdf-schema#>
+            objects:
+              on_object:
+                - datatype: xsd:date
+                - language: de
+                  predicate: rdfs:comment
+                - datatype: xsd:string
+                  of_type: <urn:Person>
+            "#,
+        )
+        .expand_rules_curie()
+        .unwrap();
+        let index = index! {
+            "<urn:Alice>" => "<urn:Person>",
+            "<urn:ACME>" => "<urn:Organization>"
+        };
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let mask = match_rules(&t, &rules, &index);
+                assert_eq!(mask.bits(), expected_mask);
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[rstest]
+    #[case("[{}]", false)]
+    #[case("[{value: {}}]", false)]
+    #[case("[{language: de}]", true)]
+    // Entries without conditions would match every object
+    fn empty_object_conditions(#[case] on_object: &str, #[case] valid_expected: bool) {
+        let rules = parse_rules(&format!("objects:\n  on_object: {on_object}\n"));
+        assert_eq!(rules.check_uris().is_ok(), valid_expected);
+        assert_eq!(rules.expand_rules_curie().is_ok(), valid_expected);
+    }
+
+    #[rstest]
+    #[case("[]", false)]
+    #[case("[\"<urn:knows>\"]", true)]
+    fn empty_propagation_path(#[case] path: &str, #[case] valid_expected: bool) {
+        let yml = format!(
+            "nodes:\n  propagate:\n    - {{from_type: \"<urn:A>\", path: {path}, as_type: \"<urn:B>\"}}\n"
+        );
+        assert_eq!(serde_yml::from_str::<Rules>(&yml).is_ok(), valid_expected);
+    }
+
+    #[rstest]
+    // Equality
+    #[case(r#"<urn:Alice> <urn:status> "HIV-positive" ."#, 0b001)]
+    #[case(r#"<urn:Alice> <urn:status> "healthy" ."#, 0b000)]
+    // Numeric comparison
+    #[case(
+        r#"<urn:Alice> <urn:age> "90"^^<http://www.w3.org/2001/XMLSchema#integer> ."#,
+        0b001
+    )]
+    #[case(r#"<urn:Alice> <urn:age> "89" ."#, 0b000)]
+    // Numeric comparison on non-numeric value
+    #[case(r#"<urn:Alice> <urn:age> "unknown" ."#, 0b000)]
+    // Set membership combined with a regex
+    #[case(r#"<urn:Alice> <urn:zip> "8001" ."#, 0b001)]
+    #[case(r#"<urn:Alice> <urn:zip> "1000" ."#, 0b000)]
+    // Value conditions never match named nodes
+    #[case(r#"<urn:Alice> <urn:status> <urn:HIV-positive> ."#, 0b000)]
+    fn value_conditions(#[case] triple: &str, #[case] expected_mask: u8) {
+        let rules = parse_rules(
+            r#"
+            objects:
+              on_object:
+                - predicate: <urn:status>
+                  value:
+                    equals: HIV-positive
+                - predicate: <urn:age>
+                  value:
+                    gt: 89
+                - predicate: <urn:zip>
+                  value:
+                    in: ["8001", "1001"]
+                    regex: '^8'
+            "#,
+        );
+        let index = index! {};
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let mask = match_rules(&t, &rules, &index);
+                assert_eq!(mask.bits(), expected_mask);
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[rstest]
+    // Link to a person through a matching predicate
+    #[case(r#"<urn:ACME> <urn:knows> <urn:Alice> ."#, 0b001)]
+    // Link to a person through another predicate
+    #[case(r#"<urn:ACME> <urn:employs> <urn:Alice> ."#, 0b000)]
+    // Link to a blank node person
+    #[case(r#"<urn:ACME> <urn:knows> _:b1 ."#, 0b001)]
+    // Subject linked to a diagnosis
+    #[case(r#"<urn:Bob> <urn:hasCondition> <urn:HIV> ."#, 0b100)]
+    fn object_type_rules(#[case] triple: &str, #[case] expected_mask: u8) {
+        let rules = parse_rules(
+            r#"
+            nodes:
+              with_object_type: ["<urn:Diagnosis>"]
+            objects:
+              on_object_type_predicate:
+                "<urn:Person>": ["<urn:knows>"]
+            "#,
+        );
+        let index = index! {
+            "<urn:Alice>" => "<urn:Person>",
+            "_:b1" => "<urn:Person>",
+            "<urn:HIV>" => "<urn:Diagnosis>"
+        };
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let mask = match_rules(&t, &rules, &index);
+                assert_eq!(mask.bits(), expected_mask);
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[rstest]
+    // Objects of excepted predicates are kept
+    #[case(
+        r#"<urn:Alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:Person> ."#,
+        0b100
+    )]
+    // Excepted type-predicate combination is kept
+    #[case(
+        r#"<urn:Person> <http://www.w3.org/2000/01/rdf-schema#label> "Person" ."#,
+        0b100
+    )]
+    // Nodes in an excepted namespace are kept
+    #[case(
+        r#"<urn:Alice> <urn:knows> <http://example.org/ontology/Bob> ."#,
+        0b100
+    )]
+    // Nodes of an excepted type are kept
+    #[case(r#"<urn:Alice> <urn:worksFor> <urn:ACME> ."#, 0b100)]
+    // Other triples are inverted
+    #[case(r#"<urn:Alice> <urn:hasName> "Alice" ."#, 0b101)]
+    fn exception_rules(#[case] triple: &str, #[case] expected_mask: u8) {
+        let rules = parse_rules(
+            r#"
+            invert: true
+            exceptions:
+              on_predicate: ["<http://www.w3.org/1999/02/22-rdf-syntax-ns#type>"]
+              of_type: ["<urn:Organization>"]
+              on_type_predicate:
+                "<http://www.w3.org/2002/07/owl#Class>": ["<http://www.w3.org/2000/01/rdf-schema#label>"]
+              matching:
+                - prefix: "<http://example.org/ontology/>"
+            "#,
+        )
+        .expand_rules_curie()
+        .unwrap();
+        let index = index! {
+            "<urn:Person>" => "<http://www.w3.org/2002/07/owl#Class>",
+            "<urn:ACME>" => "<urn:Organization>"
+        };
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let mask = match_rules(&t, &rules, &index);
+                assert_eq!(mask.bits(), expected_mask);
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[rstest]
+    // Global invert: nothing matched
+    #[case("invert: true", r#"<urn:ACME> <urn:hasAge> "200" ."#, 0b101)]
+    // Global invert: object matched by node rule only
+    #[case("invert: true", r#"<urn:ACME> <urn:hasEmployee> <urn:Bob> ."#, 0b100)]
+    // Global invert: subject and literal matched
+    #[case("invert: true", r#"<urn:Alice> <urn:hasLastName> "Foobar" ."#, 0b000)]
+    // Only objects inverted: listed predicate kept, sensitive subject replaced
+    #[case(
+        "objects: {invert: true}",
+        r#"<urn:Alice> <urn:hasLastName> "Foobar" ."#,
+        0b100
+    )]
+    // Only objects inverted: sensitive node object still replaced
+    #[case(
+        "objects: {invert: true}",
+        r#"<urn:ACME> <urn:hasEmployee> <urn:Bob> ."#,
+        0b001
+    )]
+    // Only objects inverted: other objects replaced
+    #[case("objects: {invert: true}", r#"<urn:ACME> <urn:hasAge> "200" ."#, 0b001)]
+    // Only nodes inverted: literal objects are not covered by node rules
+    #[case("nodes: {invert: true}", r#"<urn:ACME> <urn:hasAge> "200" ."#, 0b100)]
+    // Only nodes inverted: sensitive literal still replaced
+    #[case(
+        "nodes: {invert: true}",
+        r#"<urn:Alice> <urn:hasLastName> "Foobar" ."#,
+        0b001
+    )]
+    fn section_invert(#[case] invert: &str, #[case] triple: &str, #[case] expected_mask: u8) {
+        let mut rules: Rules = parse_rules(
+            r#"
+            nodes:
+              of_type: ["<urn:Person>"]
+            objects:
+              on_predicate: ["<urn:hasLastName>"]
+            "#,
+        );
+        let flags = parse_rules(invert);
+        rules.invert = flags.invert;
+        rules.nodes.invert = flags.nodes.invert;
+        rules.objects.invert = flags.objects.invert;
+
+        let index = index! {
+            "<urn:Alice>" => "<urn:Person>",
+            "<urn:Bob>" => "<urn:Person>",
+            "<urn:ACME>" => "<urn:Organization>"
+        };
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let mask = match_rules(&t, &rules, &index);
                 assert_eq!(mask.bits(), expected_mask);
                 Ok(()) as Result<(), TurtleError>
             })
             .unwrap();
     }
+
+    #[test]
+    // Exceptions override matches without invert
+    fn exception_without_invert() {
+        let rules = parse_rules(
+            r#"
+            objects:
+              on_predicate: ["<urn:hasName>"]
+            exceptions:
+              on_type_predicate:
+                "<urn:Organization>": ["<urn:hasName>"]
+            "#,
+        );
+        let index = index! { "<urn:ACME>" => "<urn:Organization>" };
+        TurtleParser::new(r#"<urn:ACME> <urn:hasName> "ACME" ."#.as_ref(), None)
+            .parse_all(&mut |t| {
+                assert!(match_rules(&t, &rules, &index).is_empty());
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
     #[rstest]
     // Prefix provided with matching cURIes
     #[case("ex", "<http://example.org/>", "ex:Person", "ex:hasName>", true)]
@@ -422,6 +2248,26 @@ mod tests {
         ));
         assert_eq!(rules.check_uris().is_ok(), match_expected);
     }
+    #[rstest]
+    // Base namespace given as cURIe
+    #[case("[base]", "ex:pseudo/", true)]
+    // Base strategy without base namespace
+    #[case("[namespace, base]", "null", false)]
+    // Namespace strategy only
+    #[case("[namespace]", "null", true)]
+    fn iri_rules(#[case] strategies: &str, #[case] base: &str, #[case] valid_expected: bool) {
+        let rules = parse_rules(&format!(
+            "
+            prefixes:
+              ex: <http://example.org/>
+            iris:
+              strategies: {strategies}
+              base: {base}
+        "
+        ));
+        assert_eq!(rules.check_uris().is_ok(), valid_expected);
+    }
+
     #[rstest]
     // Prefix provided with matching cURIes
     #[case(
@@ -464,4 +2310,168 @@ mod tests {
         let predicates = type_predicate[expanded_rule_type].clone();
         assert!(predicates.contains(expanded_rule_predicate));
     }
+
+    #[rstest]
+    #[case("")]
+    #[case("nodes: {invert: true}")]
+    #[case("objects: {invert: true}")]
+    #[case("invert: true")]
+    // Explanations always agree with the matched mask
+    fn explain_agrees(#[case] invert: &str) {
+        let mut rules = parse_rules(
+            r#"
+            nodes:
+              of_type: ["<urn:Person>"]
+              matching:
+                - prefix: "<urn:private:>"
+            objects:
+              on_predicate: ["<urn:hasLastName>"]
+              on_type_predicate:
+                "<urn:Organization>": ["<urn:hasName>"]
+              on_object:
+                - value: {gt: 100}
+            detectors: [email]
+            exceptions:
+              on_predicate: ["<urn:hasPublicName>"]
+            "#,
+        )
+        .expand_rules_curie()
+        .unwrap();
+        let flags = parse_rules(invert);
+        rules.invert = flags.invert;
+        rules.nodes.invert = flags.nodes.invert;
+        rules.objects.invert = flags.objects.invert;
+
+        let index = index! {
+            "<urn:Alice>" => "<urn:Person>",
+            "<urn:ACME>" => "<urn:Organization>"
+        };
+        let triples = r#"
+            <urn:Alice> <urn:hasLastName> "Foobar" .
+            <urn:Alice> <urn:hasPublicName> "Alice" .
+            <urn:ACME> <urn:hasName> "ACME" .
+            <urn:ACME> <urn:hasAge> "200" .
+            <urn:ACME> <urn:hasEmployee> <urn:Alice> .
+            <urn:ACME> <urn:hasContact> "info@acme.org" .
+            <urn:private:x> <urn:knows> _:b1 .
+            _:b1 <urn:hasAge> "42" .
+        "#;
+        TurtleParser::new(triples.as_ref(), None)
+            .parse_all(&mut |t| {
+                let explanation = explain_rules(&t, &rules, &index);
+                assert_eq!(
+                    explanation.mask.bits(),
+                    match_rules(&t, &rules, &index).bits(),
+                    "{t}"
+                );
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[test]
+    // Matches report the rule, entry and index type which caused them
+    fn explain_matches() {
+        let rules = parse_rules(
+            r#"
+            nodes:
+              of_type: ["<urn:Person>"]
+            objects:
+              on_type_predicate:
+                "<urn:Person>": ["<urn:hasLastName>"]
+            exceptions:
+              on_predicate: ["<urn:hasLastName>"]
+            "#,
+        );
+        let index = index! { "<urn:Alice>" => "<urn:Person>" };
+        let triple = r#"<urn:Alice> <urn:hasLastName> "Foobar" ."#;
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let explanation = explain_rules(&t, &rules, &index);
+                let rule_match = |part, rule: &'static str, value: &str, effect| RuleMatch {
+                    part,
+                    rule,
+                    value: value.to_string(),
+                    index_type: (effect != Effect::Keep).then(|| "<urn:Person>".to_string()),
+                    effect,
+                };
+                assert_eq!(explanation.mask.bits(), TripleMask::SUBJECT.bits());
+                assert_eq!(
+                    explanation.matches,
+                    vec![
+                        rule_match(
+                            Part::Subject,
+                            "nodes.of_type",
+                            "<urn:Person>",
+                            Effect::Pseudonymize
+                        ),
+                        rule_match(
+                            Part::Object,
+                            "objects.on_type_predicate",
+                            "<urn:hasLastName>",
+                            Effect::Pseudonymize
+                        ),
+                        rule_match(
+                            Part::Object,
+                            "exceptions.on_predicate",
+                            "<urn:hasLastName>",
+                            Effect::Keep
+                        ),
+                    ]
+                );
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[rstest]
+    #[case(
+        r#"<urn:Alice> <urn:note> "Mail alice@example.org" ."#,
+        Effect::PseudonymizeSpans
+    )]
+    // Spans of excepted objects are kept
+    #[case(r#"<urn:Alice> <urn:public> "Mail alice@example.org" ."#, Effect::Keep)]
+    fn explain_spans(#[case] triple: &str, #[case] expected_effect: Effect) {
+        let rules = parse_rules(
+            r#"
+            mask_spans: true
+            detectors: [email]
+            exceptions:
+              on_predicate: ["<urn:public>"]
+            "#,
+        );
+        TurtleParser::new(triple.as_ref(), None)
+            .parse_all(&mut |t| {
+                let explanation = explain_rules(&t, &rules, &index! {});
+                assert!(explanation.mask.is_empty());
+                let detected = explanation.matches.iter().find(|m| m.rule == "detectors");
+                assert_eq!(detected.unwrap().effect, expected_effect);
+                Ok(()) as Result<(), TurtleError>
+            })
+            .unwrap();
+    }
+
+    #[rstest]
+    // Typo of a nested field
+    #[case(
+        "objects:\n  on_predicates: []\n",
+        "objects: unknown field `on_predicates` (did you mean `on_predicate`?), expected one of"
+    )]
+    // Typo of a top-level field
+    #[case(
+        "detector: [email]\n",
+        "unknown field `detector` (did you mean `detectors`?)"
+    )]
+    // Typo of a pattern field
+    #[case(
+        "nodes:\n  matching:\n    - prefx: \"<urn:x>\"\n",
+        "(did you mean `prefix`?)"
+    )]
+    // No similar field
+    #[case("nodes:\n  foo: []\n", "nodes: unknown field `foo`, expected one of")]
+    fn unknown_fields(#[case] yml: &str, #[case] expected: &str) {
+        let err = serde_yml::from_str::<Rules>(yml).unwrap_err();
+        let message = suggest_field(&err.to_string());
+        assert!(message.contains(expected), "{message}");
+    }
 }
@@ -0,0 +1,253 @@
+use serde_json::{json, Value};
+use std::{io::Write, path::Path};
+
+use crate::{error::TripsuError, io, uris::KNOWN_PREFIXES};
+
+/// JSON Schema of the rules file, allowing editors to validate
+/// and complete rule files.
+pub fn rules_schema() -> Value {
+    let uri_set =
+        json!({ "type": "array", "items": { "$ref": "#/$defs/uri" }, "uniqueItems": true });
+    let known_prefixes: Vec<_> = KNOWN_PREFIXES.iter().map(|(p, _)| *p).collect();
+    let patterns = json!({ "type": "array", "items": { "$ref": "#/$defs/pattern" } });
+    let type_predicates = json!({
+        "type": "object",
+        "propertyNames": { "$ref": "#/$defs/uri" },
+        "additionalProperties": { "$ref": "#/$defs/uri_set" }
+    });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "tripsu rules",
+        "description": "Rules defining which RDF elements to pseudonymize.",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "include": {
+                "description": "Rule files merged before this one, relative to this file.",
+                "type": "array",
+                "items": { "type": "string" }
+            },
+            "invert": {
+                "description": "Invert all matchings. Deprecated: set `invert` in `nodes` and `objects`.",
+                "type": "boolean",
+                "deprecated": true
+            },
+            "prefixes": {
+                "description": "Prefixes used to expand CURIEs, mapped to full URIs in angle brackets.",
+                "type": "object",
+                "additionalProperties": { "type": "string", "pattern": "^<.*>$" }
+            },
+            "use_prefixes": {
+                "description": "Well-known prefixes used without declaring them.",
+                "type": "array",
+                "items": { "enum": known_prefixes },
+                "uniqueItems": true
+            },
+            "prefixes_from": {
+                "description": "Turtle files whose @prefix declarations are used, relative to this file.",
+                "type": "array",
+                "items": { "type": "string" }
+            },
+            "nodes": { "$ref": "#/$defs/nodes" },
+            "objects": { "$ref": "#/$defs/objects" },
+            "iris": { "$ref": "#/$defs/iris" },
+            "detectors": {
+                "description": "Built-in detectors scanning literal values.",
+                "type": "array",
+                "items": { "$ref": "#/$defs/detector" }
+            },
+            "mask_spans": {
+                "description": "Only replace the detected or regex-matched spans inside literals.",
+                "type": "boolean"
+            },
+            "exceptions": { "$ref": "#/$defs/exceptions" }
+        },
+        "$defs": {
+            "uri": {
+                "description": "Full URI in angle brackets, or CURIE.",
+                "type": "string"
+            },
+            "uri_set": uri_set,
+            "pattern": {
+                "description": "Pattern matching IRIs or literal values.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "prefix": { "description": "Match values starting with the prefix.", "type": "string" },
+                    "regex": { "description": "Match values containing a match of the regex.", "type": "string", "format": "regex" }
+                },
+                "oneOf": [{ "required": ["prefix"] }, { "required": ["regex"] }]
+            },
+            "detector": {
+                "enum": ["email", "phone", "iban", "credit_card", "ip_address", "ahv"]
+            },
+            "nodes": {
+                "description": "Rules for pseudonymizing nodes.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "invert": { "description": "Replace all nodes except the matched ones.", "type": "boolean" },
+                    "of_type": { "description": "Replace nodes with a certain type.", "$ref": "#/$defs/uri_set" },
+                    "matching": { "description": "Replace nodes whose IRI matches a pattern.", "$ref": "#/$defs/patterns" },
+                    "with_object_type": { "description": "Replace subjects whose object has a certain type.", "$ref": "#/$defs/uri_set" },
+                    "propagate": {
+                        "description": "Propagate types along property paths when indexing.",
+                        "type": "array",
+                        "items": { "$ref": "#/$defs/propagation" }
+                    }
+                }
+            },
+            "propagation": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["from_type", "path", "as_type"],
+                "properties": {
+                    "from_type": { "$ref": "#/$defs/uri" },
+                    "path": { "type": "array", "items": { "$ref": "#/$defs/uri" }, "minItems": 1 },
+                    "as_type": { "$ref": "#/$defs/uri" }
+                }
+            },
+            "objects": {
+                "description": "Rules for pseudonymizing objects.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "invert": { "description": "Replace all objects except the matched ones.", "type": "boolean" },
+                    "on_predicate": { "description": "Replace values of these predicates.", "$ref": "#/$defs/uri_set" },
+                    "on_type_predicate": { "description": "Replace values of predicates for specific subject types.", "$ref": "#/$defs/type_predicates" },
+                    "on_object_type_predicate": { "description": "Replace values of predicates when the object has a specific type.", "$ref": "#/$defs/type_predicates" },
+                    "on_predicate_matching": { "description": "Replace values of predicates whose IRI matches a pattern.", "$ref": "#/$defs/patterns" },
+                    "on_literal_matching": { "description": "Replace literal values matching a pattern.", "$ref": "#/$defs/patterns" },
+                    "on_object": {
+                        "description": "Replace objects satisfying all conditions of any entry.",
+                        "type": "array",
+                        "items": { "$ref": "#/$defs/object_condition" }
+                    }
+                }
+            },
+            "type_predicates": type_predicates,
+            "patterns": patterns,
+            "object_condition": {
+                "type": "object",
+                "additionalProperties": false,
+                "minProperties": 1,
+                "properties": {
+                    "of_type": { "description": "Type of the subject.", "$ref": "#/$defs/uri" },
+                    "predicate": { "description": "Predicate of the triple.", "$ref": "#/$defs/uri" },
+                    "datatype": { "description": "Datatype of a literal object.", "$ref": "#/$defs/uri" },
+                    "language": { "description": "Language range of a language-tagged literal object.", "type": "string" },
+                    "value": { "$ref": "#/$defs/value_condition" }
+                }
+            },
+            "value_condition": {
+                "description": "Conditions on the value of a literal object, all of which must hold.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "equals": { "type": "string" },
+                    "in": { "type": "array", "items": { "type": "string" } },
+                    "gt": { "type": "number" },
+                    "ge": { "type": "number" },
+                    "lt": { "type": "number" },
+                    "le": { "type": "number" },
+                    "regex": { "type": "string", "format": "regex" }
+                }
+            },
+            "iris": {
+                "description": "Rules defining how the IRIs of named nodes are pseudonymized.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "strategies": {
+                        "description": "Strategies tried in order until one can handle the IRI.",
+                        "type": "array",
+                        "items": { "enum": ["namespace", "base", "authority"] }
+                    },
+                    "base": { "description": "Namespace used by the `base` strategy.", "$ref": "#/$defs/uri" },
+                    "namespaces": {
+                        "description": "Replacement namespaces for original namespaces of pseudonymized IRIs.",
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/$defs/uri" }
+                    },
+                    "rewrite_all": { "description": "Rewrite namespaces of all IRIs, not only pseudonymized ones.", "type": "boolean" }
+                }
+            },
+            "exceptions": {
+                "description": "Exceptions overriding all other rules.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "of_type": { "description": "Never replace nodes with a certain type.", "$ref": "#/$defs/uri_set" },
+                    "on_predicate": { "description": "Never replace values of these predicates.", "$ref": "#/$defs/uri_set" },
+                    "on_type_predicate": { "description": "Never replace values of predicates for specific types.", "$ref": "#/$defs/type_predicates" },
+                    "matching": { "description": "Never replace nodes whose IRI matches a pattern.", "$ref": "#/$defs/patterns" }
+                }
+            }
+        }
+    })
+}
+
+/// Write the JSON Schema of the rules file to the output.
+pub fn write_rules_schema(output: &Path) -> Result<(), TripsuError> {
+    let mut buf_output = io::get_writer(output)?;
+    serde_json::to_writer_pretty(&mut buf_output, &rules_schema()).map_err(std::io::Error::from)?;
+    buf_output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use rstest::rstest;
+    use std::collections::BTreeSet;
+
+    /// Names accepted by serde, listed in the error for an unknown one.
+    fn accepted_names(yml: &str) -> BTreeSet<String> {
+        let err = serde_yml::from_str::<Rules>(yml).unwrap_err().to_string();
+        let (_, expected) = err.split_once("expected").unwrap();
+        expected
+            .split('`')
+            .skip(1)
+            .step_by(2)
+            .map(String::from)
+            .collect()
+    }
+
+    #[rstest]
+    #[case("bogus: 1", "/properties")]
+    #[case("nodes: {bogus: 1}", "/$defs/nodes/properties")]
+    #[case("nodes: {matching: [{bogus: 1}]}", "/$defs/pattern/properties")]
+    #[case("nodes: {propagate: [{bogus: 1}]}", "/$defs/propagation/properties")]
+    #[case("objects: {bogus: 1}", "/$defs/objects/properties")]
+    #[case(
+        "objects: {on_object: [{bogus: 1}]}",
+        "/$defs/object_condition/properties"
+    )]
+    #[case(
+        "objects: {on_object: [{value: {bogus: 1}}]}",
+        "/$defs/value_condition/properties"
+    )]
+    #[case("iris: {bogus: 1}", "/$defs/iris/properties")]
+    #[case("exceptions: {bogus: 1}", "/$defs/exceptions/properties")]
+    #[case("detectors: [bogus]", "/$defs/detector/enum")]
+    #[case(
+        "iris: {strategies: [bogus]}",
+        "/$defs/iris/properties/strategies/items/enum"
+    )]
+    // The schema lists exactly the names accepted by the rules
+    fn schema_matches_rules(#[case] yml: &str, #[case] pointer: &str) {
+        let schema = rules_schema();
+        let names: BTreeSet<String> = match &schema.pointer(pointer).unwrap() {
+            Value::Object(properties) => properties.keys().cloned().collect(),
+            Value::Array(variants) => variants
+                .iter()
+                .map(|v| v.as_str().unwrap().to_string())
+                .collect(),
+            _ => panic!("Unexpected schema at {pointer}"),
+        };
+
+        assert_eq!(names, accepted_names(yml));
+    }
+}
@@ -0,0 +1,237 @@
+use rio_api::parser::TriplesParser;
+use rio_turtle::{TurtleError, TurtleParser};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    error::TripsuError,
+    io,
+    log::{warn, Logger},
+    rdf_types::*,
+    rules::Rules,
+};
+
+const SH: &str = "http://www.w3.org/ns/shacl#";
+const RDF_TYPE: &str = "<http://www.w3.org/1999/02/22-rdf-syntax-ns#type>";
+const RDFS_CLASS: &str = "<http://www.w3.org/2000/01/rdf-schema#Class>";
+const OWL_CLASS: &str = "<http://www.w3.org/2002/07/owl#Class>";
+
+/// Default annotation marking shapes as sensitive.
+pub const SENSITIVE: &str = "https://github.com/sdsc-ordes/tripsu#sensitive";
+
+/// Object of a triple in a shapes graph.
+#[derive(Debug, Clone, PartialEq)]
+enum Object {
+    /// Named or blank node, as in N-Triples.
+    Node(String),
+    /// Lexical form of a literal.
+    Literal(String),
+}
+
+/// Triples of a shapes graph, indexed by subject and predicate.
+#[derive(Default)]
+struct Shapes {
+    objects: HashMap<(String, String), Vec<Object>>,
+}
+
+impl Shapes {
+    fn objects(&self, subject: &str, predicate: &str) -> &[Object] {
+        self.objects
+            .get(&(subject.to_string(), predicate.to_string()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    fn nodes<'a>(&'a self, subject: &str, predicate: &str) -> impl Iterator<Item = &'a str> {
+        self.objects(subject, predicate)
+            .iter()
+            .filter_map(|o| match o {
+                Object::Node(n) => Some(n.as_str()),
+                Object::Literal(_) => None,
+            })
+    }
+
+    /// Whether the node carries the annotation with a true value.
+    fn is_annotated(&self, node: &str, annotation: &str) -> bool {
+        self.objects(node, annotation).iter().any(|o| match o {
+            Object::Literal(v) => matches!(v.as_str(), "true" | "1"),
+            Object::Node(_) => false,
+        })
+    }
+
+    /// Classes targeted by a node shape. A shape which is
+    /// also a class implicitly targets itself.
+    fn target_classes<'a>(&'a self, shape: &'a str) -> Vec<&'a str> {
+        let mut classes: Vec<&str> = self.nodes(shape, &sh("targetClass")).collect();
+        if self
+            .nodes(shape, RDF_TYPE)
+            .any(|t| t == RDFS_CLASS || t == OWL_CLASS)
+        {
+            classes.push(shape);
+        }
+        classes
+    }
+}
+
+fn sh(name: &str) -> String {
+    format!("<{SH}{name}>")
+}
+
+/// Build rules from the SHACL shapes of a turtle document.
+/// Values of the `sh:path` of property shapes carrying the annotation are
+/// pseudonymized for instances of the target classes of their node shape,
+/// and instances of the target classes of annotated node shapes are
+/// pseudonymized. Returns the rules and the annotated paths which could
+/// not be converted, as only predicate paths are supported.
+pub fn rules_from_shacl(
+    reader: impl BufRead,
+    annotation: &str,
+) -> Result<(Rules, Vec<String>), TurtleError> {
+    let mut shapes = Shapes::default();
+    // Subjects in the order of the document, to convert shapes in order
+    let mut subjects = Vec::new();
+    let mut seen = HashSet::new();
+    TurtleParser::new(reader, None).parse_all(&mut |t: TripleView| {
+        let object = match t.object {
+            TermView::Literal(LiteralView::Simple { value })
+            | TermView::Literal(LiteralView::LanguageTaggedString { value, .. })
+            | TermView::Literal(LiteralView::Typed { value, .. }) => {
+                Object::Literal(value.to_string())
+            }
+            o => Object::Node(o.to_string()),
+        };
+        let subject = t.subject.to_string();
+        if seen.insert(subject.clone()) {
+            subjects.push(subject.clone());
+        }
+        shapes
+            .objects
+            .entry((subject, t.predicate.to_string()))
+            .or_default()
+            .push(object);
+        Result::<(), TurtleError>::Ok(())
+    })?;
+
+    let annotation = format!("<{annotation}>");
+    let mut rules = Rules::default();
+    let mut unsupported = Vec::new();
+    for shape in subjects.iter() {
+        let classes = shapes.target_classes(shape);
+        if classes.is_empty() {
+            continue;
+        }
+        if shapes.is_annotated(shape, &annotation) {
+            for class in classes.iter() {
+                rules.add_node_type(class.to_string());
+            }
+        }
+        for property in shapes.nodes(shape, &sh("property")) {
+            if !shapes.is_annotated(property, &annotation) {
+                continue;
+            }
+            for path in shapes.nodes(property, &sh("path")) {
+                // Complex paths are blank nodes
+                if path.starts_with("_:") {
+                    unsupported.push(format!("{shape} sh:property {property}"));
+                    continue;
+                }
+                for class in classes.iter() {
+                    rules.add_type_predicate(class.to_string(), path.to_string());
+                }
+            }
+        }
+    }
+
+    Ok((rules, unsupported))
+}
+
+/// Convert the SHACL shapes of a turtle file to a yaml rules file.
+pub fn shacl_to_rules(
+    log: &Logger,
+    input: &Path,
+    output: &Path,
+    annotation: &str,
+) -> Result<(), TripsuError> {
+    let buf_input = io::get_reader(input)?;
+
+    let (rules, unsupported) = rules_from_shacl(buf_input, annotation)?;
+    for shape in unsupported {
+        warn!(log, "Only predicate paths are supported, skipping: {shape}");
+    }
+    let yaml = serde_yml::to_string(&rules)
+        .map_err(|e| TripsuError::Rules(format!("Cannot serialize rules: {e}")))?;
+    let mut buf_output = io::get_writer(output)?;
+    buf_output.write_all(yaml.as_bytes())?;
+    buf_output.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Annotated property and node shapes are converted to rules
+    fn shacl_rules() {
+        let shapes = r#"
+            @prefix sh: <http://www.w3.org/ns/shacl#> .
+            @prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+            @prefix tripsu: <https://github.com/sdsc-ordes/tripsu#> .
+            @prefix ex: <http://example.org/> .
+
+            ex:PersonShape a sh:NodeShape ;
+                sh:targetClass ex:Person ;
+                sh:property [ sh:path ex:lastName ; tripsu:sensitive true ] ;
+                sh:property [ sh:path ex:firstName ; tripsu:sensitive true ] ;
+                sh:property [ sh:path ex:age ; tripsu:sensitive false ] ;
+                sh:property [ sh:path ( ex:address ex:street ) ; tripsu:sensitive true ] .
+
+            ex:Patient a sh:NodeShape, rdfs:Class ;
+                tripsu:sensitive true ;
+                sh:property [ sh:path ex:diagnosis ; tripsu:sensitive "true" ] .
+
+            ex:OrganizationShape a sh:NodeShape ;
+                sh:targetClass ex:Organization ;
+                sh:property [ sh:path ex:name ] .
+        "#;
+
+        let (rules, unsupported) = rules_from_shacl(shapes.as_bytes(), SENSITIVE).unwrap();
+        let mut entries: Vec<_> = rules
+            .uri_entries()
+            .iter()
+            .map(|e| format!("{} {}", e.field, e.uri))
+            .collect();
+        entries.sort();
+
+        assert_eq!(
+            entries,
+            vec![
+                "nodes.of_type <http://example.org/Patient>",
+                "objects.on_type_predicate <http://example.org/Patient>",
+                "objects.on_type_predicate <http://example.org/Person>",
+                "objects.on_type_predicate <http://example.org/diagnosis>",
+                "objects.on_type_predicate <http://example.org/firstName>",
+                "objects.on_type_predicate <http://example.org/lastName>",
+            ]
+        );
+        assert_eq!(unsupported.len(), 1);
+        // Rules are written in a stable order
+        assert_eq!(
+            serde_yml::to_string(&rules).unwrap(),
+            r#"nodes:
+  of_type:
+  - <http://example.org/Patient>
+objects:
+  on_type_predicate:
+    <http://example.org/Patient>:
+    - <http://example.org/diagnosis>
+    <http://example.org/Person>:
+    - <http://example.org/firstName>
+    - <http://example.org/lastName>
+"#
+        );
+    }
+}
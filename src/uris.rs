@@ -22,7 +22,7 @@ impl Uri {
         }
     }
     pub fn is_compact(&self) -> bool {
-        return !self.is_full();
+        !self.is_full()
     }
 
     pub fn expand(&self, prefix_map: &PrefixMap) -> Result<Self, PrefixError> {
@@ -81,6 +81,27 @@ impl TryInto<sophia_iri::Iri<String>> for Uri {
     }
 }
 
+/// Well-known prefixes which rules can use without declaring them.
+pub const KNOWN_PREFIXES: &[(&str, &str)] = &[
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+    ("owl", "http://www.w3.org/2002/07/owl#"),
+    ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+    ("foaf", "http://xmlns.com/foaf/0.1/"),
+    ("schema", "http://schema.org/"),
+    ("dcterms", "http://purl.org/dc/terms/"),
+    ("skos", "http://www.w3.org/2004/02/skos/core#"),
+    ("prov", "http://www.w3.org/ns/prov#"),
+];
+
+/// Get the namespace of a well-known prefix.
+pub fn known_prefix(name: &str) -> Option<&'static str> {
+    KNOWN_PREFIXES
+        .iter()
+        .find(|(prefix, _)| *prefix == name)
+        .map(|(_, namespace)| *namespace)
+}
+
 /// Errors related to CURIE prefixes
 #[derive(Debug)]
 pub enum PrefixError {